@@ -49,7 +49,27 @@
                             apps = lib.mkOption {
                               type = lib.types.listOf lib.types.str;
                               default = [ ];
-                              description = "List of app IDs to skip during session restore";
+                              description = "Launch commands whose windows are skipped";
+                            };
+                            app_ids = lib.mkOption {
+                              type = lib.types.listOf lib.types.str;
+                              default = [ ];
+                              description = "App IDs whose windows are skipped, as globs or /regex/";
+                            };
+                            titles = lib.mkOption {
+                              type = lib.types.listOf lib.types.str;
+                              default = [ ];
+                              description = "Regexes matching the titles of windows to skip";
+                            };
+                            workspaces = lib.mkOption {
+                              type = lib.types.listOf lib.types.str;
+                              default = [ ];
+                              description = "Names of workspaces whose windows are skipped, as globs or /regex/";
+                            };
+                            outputs = lib.mkOption {
+                              type = lib.types.listOf lib.types.str;
+                              default = [ ];
+                              description = "Outputs whose windows are skipped, as globs or /regex/";
                             };
                           };
                         };
@@ -57,9 +77,15 @@
                         description = "Applications to skip";
                       };
                       launch = lib.mkOption {
-                        type = lib.types.attrsOf lib.types.str;
+                        type = lib.types.attrsOf (
+                          lib.types.oneOf [
+                            lib.types.str
+                            (lib.types.listOf lib.types.str)
+                            (pkgs.formats.toml { }).type
+                          ]
+                        );
                         default = { };
-                        description = "Map app_id to actual launch command";
+                        description = "Map app_id to its launch command, argument list, or a table with command, env, cwd, after, wait_for and timeout";
                       };
                     };
                   };
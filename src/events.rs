@@ -0,0 +1,185 @@
+use std::{
+   sync::mpsc::{
+      self,
+      Receiver,
+   },
+   thread,
+   time::{
+      Duration,
+      Instant,
+   },
+};
+
+use log::debug;
+use niri_ipc::{
+   Event,
+   Reply,
+   Request,
+   Response,
+   Window,
+   Workspace,
+   socket::Socket,
+   state::{
+      EventStreamState,
+      EventStreamStatePart as _,
+   },
+};
+
+use crate::{
+   NiriError,
+   NiriResult,
+};
+
+/// How long to wait before retrying a failed save, doubled with every failure
+const SAVE_RETRY: Duration = Duration::from_secs(5);
+
+/// Longest wait between two attempts to save
+const MAX_SAVE_RETRY: Duration = Duration::from_mins(5);
+
+/// In-memory model of Niri's windows and workspaces, kept up to date from the
+/// IPC event stream.
+#[derive(Default)]
+pub struct SessionState {
+   state:         EventStreamState,
+   /// Whether the initial window list has been received. Until then the model
+   /// is empty and must not be written out.
+   ready:         bool,
+   /// When the layout last changed without being saved yet, or saving it
+   /// last failed.
+   last_change:   Option<Instant>,
+   /// How many saves in a row have failed
+   save_failures: u32,
+}
+
+impl SessionState {
+   pub fn apply(&mut self, event: Event) {
+      let changes_layout = matches!(
+         event,
+         Event::WindowsChanged { .. }
+            | Event::WindowOpenedOrChanged { .. }
+            | Event::WindowClosed { .. }
+            | Event::WindowLayoutsChanged { .. }
+            | Event::WorkspacesChanged { .. }
+      );
+
+      if matches!(event, Event::WindowsChanged { .. }) {
+         self.ready = true;
+      }
+
+      self.state.apply(event);
+
+      if changes_layout {
+         self.last_change = Some(Instant::now());
+      }
+   }
+
+   pub const fn is_ready(&self) -> bool {
+      self.ready
+   }
+
+   /// Whether there are unsaved changes and no further change happened for
+   /// `debounce`. After a failed save, the next one waits longer.
+   pub fn is_settled(&self, debounce: Duration) -> bool {
+      let retry = self
+         .save_failures
+         .checked_sub(1)
+         .map_or(Duration::ZERO, |doublings| {
+            SAVE_RETRY
+               .saturating_mul(2_u32.saturating_pow(doublings))
+               .min(MAX_SAVE_RETRY)
+         });
+      self.ready
+         && self
+            .last_change
+            .is_some_and(|changed| changed.elapsed() >= debounce.max(retry))
+   }
+
+   pub const fn mark_saved(&mut self) {
+      self.last_change = None;
+      self.save_failures = 0;
+   }
+
+   /// Keep the changes unsaved, and back off before trying again
+   pub fn mark_save_failed(&mut self) {
+      self.last_change = Some(Instant::now());
+      self.save_failures = self.save_failures.saturating_add(1);
+   }
+
+   pub fn window(&self, id: u64) -> Option<&Window> {
+      self.state.windows.windows.get(&id)
+   }
+
+   /// The window that has keyboard focus, if any
+   pub fn focused_window(&self) -> Option<u64> {
+      self
+         .state
+         .windows
+         .windows
+         .values()
+         .find(|window| window.is_focused)
+         .map(|window| window.id)
+   }
+
+   /// Current windows, ordered by id so that saved sessions are stable.
+   pub fn windows(&self) -> Vec<Window> {
+      let mut windows = self
+         .state
+         .windows
+         .windows
+         .values()
+         .cloned()
+         .collect::<Vec<_>>();
+      windows.sort_by_key(|w| w.id);
+      windows
+   }
+
+   pub fn workspaces(&self) -> Vec<Workspace> {
+      let mut workspaces = self
+         .state
+         .workspaces
+         .workspaces
+         .values()
+         .cloned()
+         .collect::<Vec<_>>();
+      workspaces.sort_by_key(|w| w.id);
+      workspaces
+   }
+}
+
+/// Subscribe to Niri's event stream.
+///
+/// Events are read on a background thread and forwarded through the returned
+/// channel. The channel disconnects once the stream ends, e.g. when Niri exits.
+pub fn subscribe() -> NiriResult<Receiver<Event>> {
+   let mut socket = Socket::connect().map_err(NiriError::Connect)?;
+   match socket.send(Request::EventStream).map_err(NiriError::Send)? {
+      Reply::Ok(Response::Handled) => {},
+      Reply::Ok(other) => {
+         return Err(NiriError::Reply(format!(
+            "Unexpected response from Niri: {other:?}"
+         )));
+      },
+      Reply::Err(err) => return Err(NiriError::Reply(err)),
+   }
+
+   let (sender, receiver) = mpsc::channel();
+   let mut read_event = socket.read_events();
+
+   thread::spawn(move || {
+      loop {
+         match read_event() {
+            Ok(event) => {
+               if sender.send(event).is_err() {
+                  break;
+               }
+            },
+            Err(err) => {
+               debug!("event stream closed: {err}");
+               break;
+            },
+         }
+      }
+   });
+
+   Ok(receiver)
+}
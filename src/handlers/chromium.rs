@@ -0,0 +1,158 @@
+use std::{
+   env,
+   path::Path,
+};
+
+use log::debug;
+
+use super::{
+   AppHandler,
+   Launch,
+};
+
+/// Chromium-based browsers, by the prefix of the app ids of their web app
+/// windows, with the names their executable goes by
+const BROWSERS: &[(&str, &[&str])] = &[
+   ("chrome", &["google-chrome-stable", "google-chrome"]),
+   ("chromium", &["chromium", "chromium-browser"]),
+   ("brave", &["brave", "brave-browser"]),
+   ("vivaldi", &["vivaldi", "vivaldi-stable"]),
+   ("thorium", &["thorium-browser"]),
+   ("msedge", &["microsoft-edge-stable", "microsoft-edge"]),
+];
+
+/// App ids of regular Chromium-based browser windows
+const BROWSER_APP_IDS: &[&str] = &[
+   "google-chrome",
+   "chromium",
+   "chromium-browser",
+   "brave-browser",
+   "vivaldi-stable",
+   "thorium-browser",
+];
+
+/// Chromium-based browsers, relaunched with the user data directory a window
+/// belonged to. Web app windows are reopened as that web app.
+///
+/// The profiles of a user data directory all share one browser process, whose
+/// command line only names the profile it was started with, so regular windows
+/// of other profiles open in the last used profile.
+pub struct Chromium;
+
+impl AppHandler for Chromium {
+   fn name(&self) -> &'static str {
+      "chromium"
+   }
+
+   fn matches(&self, launch: &Launch) -> bool {
+      BROWSER_APP_IDS.contains(&launch.app_id) || WebApp::parse(launch.app_id).is_some()
+   }
+
+   fn build_command(&self, launch: &Launch) -> Vec<String> {
+      let mut command = launch.command.clone();
+
+      // A launch command from the config may well be a wrapper script that
+      // already opens the right app, so only the bare app id is replaced
+      if let Some(web_app) = WebApp::parse(launch.app_id)
+         && command.as_slice() == [launch.app_id]
+      {
+         debug!("relaunching {} as a web app", launch.app_id);
+         return web_app.command();
+      }
+
+      // Every user data directory runs its own browser process, so the one on
+      // the command line is the window's
+      let option = "--user-data-dir";
+      if let Some(value) = launch
+         .cmdline
+         .and_then(|cmdline| option_value(cmdline, option))
+         && !command.iter().any(|arg| arg.starts_with(option))
+      {
+         command.push(format!("{option}={value}"));
+      }
+      command
+   }
+}
+
+/// A web app window, e.g. `chrome-discord.com__app-Default` for
+/// `https://discord.com/app` in the default profile, or
+/// `brave-<32 letter id>-Profile_1` for an installed app
+struct WebApp<'app_id> {
+   executables: &'static [&'static str],
+   /// The app id Chromium knows the installed app by, or the host and path of
+   /// the site with slashes replaced by underscores
+   app:         &'app_id str,
+   /// Name of the profile directory, with spaces replaced by underscores
+   profile:     &'app_id str,
+}
+
+impl<'app_id> WebApp<'app_id> {
+   fn parse(app_id: &'app_id str) -> Option<Self> {
+      let (prefix, rest) = app_id.split_once('-')?;
+      let (app, profile) = rest.rsplit_once('-')?;
+      let &(_, executables) = BROWSERS.iter().find(|&&(browser, _)| browser == prefix)?;
+      if app.is_empty() || !is_profile_directory(profile) || !(is_app_id(app) || app.contains("__"))
+      {
+         return None;
+      }
+
+      Some(Self {
+         executables,
+         app,
+         profile,
+      })
+   }
+
+   fn command(&self) -> Vec<String> {
+      let app = if is_app_id(self.app) {
+         format!("--app-id={}", self.app)
+      } else {
+         // `example.com__path_to_page` is `example.com/path/to/page`
+         let (host, path) = self.app.split_once("__").unwrap_or((self.app, ""));
+         format!("--app=https://{host}/{}", path.replace('_', "/"))
+      };
+
+      vec![
+         find_executable(self.executables),
+         format!("--profile-directory={}", self.profile.replace('_', " ")),
+         app,
+      ]
+   }
+}
+
+/// Whether `name` is the id of an installed Chromium app: 32 letters from `a`
+/// to `p`
+fn is_app_id(name: &str) -> bool {
+   name.len() == 32 && name.bytes().all(|byte| matches!(byte, b'a'..=b'p'))
+}
+
+/// Whether `name` looks like a Chromium profile directory, `Default` or
+/// `Profile_N`
+fn is_profile_directory(name: &str) -> bool {
+   name == "Default"
+      || name.strip_prefix("Profile_").is_some_and(|number| {
+         !number.is_empty() && number.bytes().all(|byte| byte.is_ascii_digit())
+      })
+}
+
+/// The first of `names` that is on `$PATH`, or else the first one
+fn find_executable(names: &[&str]) -> String {
+   let path = env::var_os("PATH").unwrap_or_default();
+   names
+      .iter()
+      .find(|&&name| env::split_paths(&path).any(|dir| Path::new(&dir).join(name).is_file()))
+      .or_else(|| names.first())
+      .map_or_else(String::new, |&name| name.to_owned())
+}
+
+/// The value of `--option=value` or `--option value` on a command line
+fn option_value<'cmdline>(cmdline: &'cmdline [String], option: &str) -> Option<&'cmdline str> {
+   cmdline.iter().enumerate().find_map(|(idx, arg)| {
+      let rest = arg.strip_prefix(option)?;
+      match rest.strip_prefix('=') {
+         Some(value) => Some(value),
+         None if rest.is_empty() => cmdline.get(idx + 1).map(String::as_str),
+         None => None,
+      }
+   })
+}
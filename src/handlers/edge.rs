@@ -0,0 +1,134 @@
+use std::{
+   fs,
+   iter,
+   path::Path,
+};
+
+use log::debug;
+
+use super::{
+   AppHandler,
+   Launch,
+};
+
+/// Microsoft Edge with the Workspaces feature, reopened with the workspace
+/// named in the window title
+pub struct Edge;
+
+/// Config directories of the Edge release channels, relative to
+/// `$XDG_CONFIG_HOME`
+const CHANNELS: &[&str] = &["microsoft-edge", "microsoft-edge-beta", "microsoft-edge-dev"];
+
+/// An Edge workspace found in a profile's cache
+struct Workspace {
+   /// Name of the profile directory, e.g. `Default` or `Profile 1`
+   profile: String,
+   /// The UUID of the workspace
+   id:      String,
+}
+
+impl AppHandler for Edge {
+   fn name(&self) -> &'static str {
+      "edge"
+   }
+
+   fn matches(&self, launch: &Launch) -> bool {
+      CHANNELS.contains(&launch.app_id)
+   }
+
+   fn build_command(&self, launch: &Launch) -> Vec<String> {
+      // Window title IS the workspace name (e.g., "vllm", "work", "personal")
+      // We look up the workspace ID from Edge's cache and launch with --launch-workspace
+      // Note: the command may contain args (e.g., "microsoft-edge-stable --force-device-scale-factor=1.1")
+      let mut cmd_parts = launch.command.clone();
+      if let Some(workspace_name) = launch.title {
+         if let Some(workspace) = find_workspace(launch.app_id, workspace_name) {
+            debug!(
+               "found Edge workspace ID for '{workspace_name}' in profile '{}': {}",
+               workspace.profile, workspace.id
+            );
+            // Launch with workspace:
+            // `microsoft-edge-stable [args] --profile-directory=<dir> --launch-workspace=<uuid>`
+            if !cmd_parts.iter().any(|arg| arg.starts_with("--profile-directory")) {
+               cmd_parts.push(format!("--profile-directory={}", workspace.profile));
+            }
+            cmd_parts.push(format!("--launch-workspace={}", workspace.id));
+         } else {
+            // Workspace not found in cache (maybe deleted or new profile)
+            debug!("no Edge workspace found for '{workspace_name}'");
+         }
+      }
+      cmd_parts
+   }
+}
+
+/// Find a Microsoft Edge workspace by its name, in any profile.
+///
+/// Edge stores workspace information of each profile in a JSON cache file at:
+/// `~/.config/microsoft-edge/<profile>/Workspaces/WorkspacesCache`
+///
+/// The window title of an Edge workspace window is the workspace name itself,
+/// so we can use it to look up the corresponding workspace ID.
+///
+/// Edge can then be launched with `--profile-directory=<profile>
+/// --launch-workspace=<ID>` to open that workspace.
+///
+/// The config directory of the channel the window belongs to is searched
+/// first, then those of the other channels (beta, dev).
+///
+/// # Arguments
+/// * `app_id` - The app id of the window, which tells its channel
+/// * `workspace_name` - The workspace name (from window title)
+///
+/// # Returns
+/// * `Some(workspace)` - The profile and UUID of the workspace
+/// * `None` - If no cache file has a workspace of that name
+fn find_workspace(app_id: &str, workspace_name: &str) -> Option<Workspace> {
+   let config_dir = dirs::config_dir()?;
+   let channels = iter::once(app_id).chain(
+      CHANNELS
+         .iter()
+         .copied()
+         .filter(|&channel| channel != app_id),
+   );
+
+   channels.map(|channel| config_dir.join(channel)).find_map(|channel_dir| {
+      // Profiles are `Default`, `Profile 1`, `Profile 2` and so on. Sorted so
+      // that the same profile wins every time if several have the workspace.
+      let mut profiles = fs::read_dir(&channel_dir)
+         .ok()?
+         .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+         .filter(|name| name == "Default" || name.starts_with("Profile "))
+         .collect::<Vec<_>>();
+      profiles.sort_unstable();
+
+      profiles.into_iter().find_map(|profile| {
+         let cache_path = channel_dir.join(&profile).join("Workspaces/WorkspacesCache");
+         let id = get_workspace_id(&cache_path, workspace_name)?;
+         Some(Workspace { profile, id })
+      })
+   })
+}
+
+/// Get Microsoft Edge workspace ID from workspace name, in a single profile's
+/// workspace cache.
+///
+/// # Example
+/// ```
+/// // Workspace name: "vllm"
+/// // Returns: Some("bbb831b3-9481-4fa9-872e-2b7344417362")
+/// ```
+fn get_workspace_id(cache_path: &Path, workspace_name: &str) -> Option<String> {
+   // Read and parse the JSON cache file
+   let cache_content = fs::read_to_string(cache_path).ok()?;
+   let cache: serde_json::Value = serde_json::from_str(&cache_content).ok()?;
+
+   // Search for a workspace with matching name and return its ID
+   // Cache structure: { "workspaces": [{ "name": "...", "id": "..." }, ...] }
+   cache["workspaces"]
+      .as_array()?
+      .iter()
+      .find(|ws| ws["name"].as_str() == Some(workspace_name))
+      .and_then(|ws| ws["id"].as_str())
+      .map(|id| id.to_string())
+}
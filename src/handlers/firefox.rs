@@ -0,0 +1,50 @@
+use log::debug;
+
+use super::{
+   AppHandler,
+   Launch,
+};
+
+/// Firefox and its forks, relaunched with the profile a window belonged to.
+///
+/// All windows have the same app id whatever their profile, so the profile is
+/// taken from the command line recorded when the session was saved.
+pub struct Firefox;
+
+impl AppHandler for Firefox {
+   fn name(&self) -> &'static str {
+      "firefox"
+   }
+
+   fn matches(&self, launch: &Launch) -> bool {
+      launch.app_id.starts_with("firefox") || launch.app_id == "librewolf"
+   }
+
+   fn build_command(&self, launch: &Launch) -> Vec<String> {
+      let mut command = launch.command.clone();
+      if command.iter().any(|arg| is_profile_option(arg)) {
+         return command;
+      }
+
+      // `-P name` selects a profile by name, `--profile path` by directory
+      let profile = launch.cmdline.and_then(|cmdline| {
+         cmdline.windows(2).find_map(|pair| {
+            match *pair {
+               [ref option, ref value] if is_profile_option(option) && !value.starts_with('-') => {
+                  Some((option, value))
+               },
+               _ => None,
+            }
+         })
+      });
+      if let Some((option, value)) = profile {
+         debug!("relaunching {} with profile {value}", launch.app_id);
+         command.extend([option.clone(), value.clone()]);
+      }
+      command
+   }
+}
+
+fn is_profile_option(arg: &str) -> bool {
+   matches!(arg, "-P" | "-p" | "--profile" | "-profile")
+}
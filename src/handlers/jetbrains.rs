@@ -0,0 +1,313 @@
+use std::{
+   fs,
+   path::{
+      Path,
+      PathBuf,
+   },
+};
+
+use log::debug;
+
+use super::{
+   AppHandler,
+   Launch,
+};
+use crate::launch::expand_home;
+
+/// Config directory names of the IDEs whose app id doesn't name them, in
+/// lowercase and without the version
+const PRODUCTS: &[(&str, &[&str])] = &[
+   ("idea", &["intellijidea", "ideaic"]),
+   ("idea-ce", &["ideaic"]),
+   ("pycharm-ce", &["pycharmce"]),
+];
+
+/// JetBrains IDEs (PyCharm, IntelliJ, WebStorm, etc.), reopened with the
+/// project from the window title
+pub struct JetBrains;
+
+impl AppHandler for JetBrains {
+   fn name(&self) -> &'static str {
+      "jetbrains"
+   }
+
+   fn matches(&self, launch: &Launch) -> bool {
+      launch.app_id.starts_with("jetbrains-")
+   }
+
+   fn build_command(&self, launch: &Launch) -> Vec<String> {
+      // Window title format: "project_name [/path/to/project] – filename"
+      // We extract the path and pass it as an argument to open the correct project.
+      // Without the path in the title, the project name is looked up in the
+      // IDE's list of recent projects instead.
+      let project_path = launch.title.and_then(|title| {
+         extract_project_path(title).or_else(|| find_recent_project(launch.app_id, title))
+      });
+      if let Some(project_path) = project_path {
+         debug!("extracted project path for {}: {project_path}", launch.app_id);
+         // Launch with project path: `pycharm /home/user/projects/myproject`
+         let mut command = launch.command.clone();
+         command.push(project_path);
+         command
+      } else {
+         // Fallback: launch without project path (will open last project or welcome screen)
+         launch.command.clone()
+      }
+   }
+}
+
+/// Extract project path from JetBrains IDE window title.
+///
+/// JetBrains IDEs (PyCharm, IntelliJ, etc.) display the project path in the window title
+/// in the format: "project_name [/path/to/project] – current_file.ext"
+///
+/// This function extracts the first absolute or `~` path between square brackets, so
+/// brackets in the project or file name are skipped, and expands `~` to the home directory.
+///
+/// # Arguments
+/// * `title` - The window title from niri IPC
+///
+/// # Returns
+/// * `Some(path)` - The expanded absolute path to the project
+/// * `None` - If the title doesn't contain a valid path in brackets
+///
+/// # Example
+/// ```
+/// // Input:  "camel [~/projects/camel] – main[1].py"
+/// // Output: Some("/home/user/projects/camel")
+/// ```
+fn extract_project_path(title: &str) -> Option<String> {
+   // The path starts right after `[` and ends at the `]` closing the
+   // bracketed part, which is followed by a space or the end of the title
+   let start = ["[/", "[~"]
+      .iter()
+      .filter_map(|open| title.find(open))
+      .min()?
+      + 1;
+   let rest = &title[start..];
+   let end = rest.match_indices(']').map(|(end, _)| end).find(|&end| {
+      rest[end + 1..]
+         .chars()
+         .next()
+         .is_none_or(char::is_whitespace)
+   })?;
+
+   expand_home(Path::new(&rest[..end])).into_os_string().into_string().ok()
+}
+
+/// The project name at the start of a window title, e.g. `camel` in
+/// "camel – main.py"
+fn project_name(title: &str) -> Option<&str> {
+   let end = [" [/", " [~", " – ", " - "]
+      .iter()
+      .filter_map(|separator| title.find(separator))
+      .min()
+      .unwrap_or(title.len());
+   Some(title[..end].trim()).filter(|name| !name.is_empty())
+}
+
+/// A project from an IDE's `recentProjects.xml`
+struct RecentProject {
+   path:        PathBuf,
+   /// The name the project was given in the IDE, if it was renamed
+   name:        Option<String>,
+   /// Title of the project's window when it was last open
+   frame_title: Option<String>,
+   /// Whether the project was open when the IDE last saved the list
+   opened:      bool,
+   /// When the project window was last focused, in milliseconds since the
+   /// epoch
+   activated:   u64,
+}
+
+impl RecentProject {
+   fn has_name(&self, name: &str) -> bool {
+      self.name.as_deref().map_or_else(
+         || self.path.file_name().is_some_and(|file_name| file_name == name),
+         |own_name| own_name == name,
+      )
+   }
+}
+
+/// Find the path of the project shown in a window title, by its name, in the
+/// recent projects of the IDE.
+///
+/// JetBrains IDEs keep the projects they had open in
+/// `~/.config/JetBrains/<Product><Version>/options/recentProjects.xml`, along
+/// with the title of each project's window. A project whose window had exactly
+/// this title wins, then any project of the same name that was open, then the
+/// one used most recently.
+///
+/// # Arguments
+/// * `app_id` - The app id of the window, e.g. `jetbrains-pycharm`
+/// * `title` - The window title from niri IPC
+///
+/// # Returns
+/// * `Some(path)` - The path of a project that still exists
+/// * `None` - If no recent project has the name from the title
+fn find_recent_project(app_id: &str, title: &str) -> Option<String> {
+   let name = project_name(title)?;
+   let projects = product_config_dirs(app_id)
+      .iter()
+      .flat_map(|dir| read_recent_projects(&dir.join("options/recentProjects.xml")))
+      .filter(|project| project.path.is_dir())
+      .collect::<Vec<_>>();
+
+   let project = projects
+      .iter()
+      .find(|project| project.frame_title.as_deref() == Some(title))
+      .or_else(|| {
+         projects
+            .iter()
+            .filter(|project| project.has_name(name))
+            .max_by_key(|project| (project.opened, project.activated))
+      })?;
+   debug!("found recent project {name:?} of {app_id} at {}", project.path.display());
+   project.path.clone().into_os_string().into_string().ok()
+}
+
+/// The config directories of the IDE with this app id, newest version first
+fn product_config_dirs(app_id: &str) -> Vec<PathBuf> {
+   let Some(product) = app_id.strip_prefix("jetbrains-") else {
+      return Vec::new();
+   };
+   let prefixes = PRODUCTS
+      .iter()
+      .find(|&&(id, _)| id == product)
+      .map_or_else(
+         || vec![product.to_owned(), format!("{product}ce")],
+         |&(_, names)| names.iter().map(|&name| name.to_owned()).collect(),
+      );
+
+   let Some(jetbrains_dir) = dirs::config_dir().map(|dir| dir.join("JetBrains")) else {
+      return Vec::new();
+   };
+   let Ok(entries) = fs::read_dir(&jetbrains_dir) else {
+      return Vec::new();
+   };
+
+   // Directories are named like `PyCharm2024.3` or `PyCharmCE2024.3`
+   let mut dirs = entries
+      .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+      .filter_map(|dir_name| {
+         let lowercase = dir_name.to_lowercase();
+         let version = prefixes.iter().find_map(|prefix| {
+            lowercase
+               .strip_prefix(prefix.as_str())
+               .filter(|version| version.starts_with(|first: char| first.is_ascii_digit()))
+               .map(str::to_owned)
+         })?;
+         Some((version, dir_name))
+      })
+      .collect::<Vec<_>>();
+   dirs.sort_unstable_by(|left, right| right.cmp(left));
+
+   dirs.into_iter()
+      .map(|(_, dir_name)| jetbrains_dir.join(dir_name))
+      .collect()
+}
+
+/// Read the projects from a `recentProjects.xml`, which looks like
+///
+/// ```xml
+/// <application>
+///   <component name="RecentProjectsManager">
+///     <option name="additionalInfo">
+///       <map>
+///         <entry key="$USER_HOME$/projects/camel">
+///           <value>
+///             <RecentProjectMetaInfo frameTitle="camel – main.py" opened="true">
+///               <option name="activationTimestamp" value="1735689600000" />
+/// ```
+fn read_recent_projects(path: &Path) -> Vec<RecentProject> {
+   let Ok(content) = fs::read_to_string(path) else {
+      return Vec::new();
+   };
+   let document = match roxmltree::Document::parse(&content) {
+      Ok(document) => document,
+      Err(err) => {
+         debug!("failed to parse {}: {err}", path.display());
+         return Vec::new();
+      },
+   };
+   let home = dirs::home_dir();
+
+   document
+      .descendants()
+      .filter(|node| node.has_tag_name("entry"))
+      .filter_map(|entry| {
+         let key = entry.attribute("key")?;
+         let info = entry
+            .descendants()
+            .find(|node| node.has_tag_name("RecentProjectMetaInfo"))?;
+         let activated = info
+            .children()
+            .find(|node| {
+               node.has_tag_name("option") && node.attribute("name") == Some("activationTimestamp")
+            })
+            .and_then(|option| option.attribute("value")?.parse().ok())
+            .unwrap_or(0);
+         let path = match (key.strip_prefix("$USER_HOME$"), &home) {
+            (Some(rest), &Some(ref home)) => home.join(rest.trim_start_matches('/')),
+            _ => PathBuf::from(key),
+         };
+
+         Some(RecentProject {
+            path,
+            name: info.attribute("displayName").map(str::to_owned),
+            frame_title: info.attribute("frameTitle").map(str::to_owned),
+            opened: info.attribute("opened") == Some("true"),
+            activated,
+         })
+      })
+      .collect()
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   #[test]
+   fn extracts_project_path() {
+      assert_eq!(
+         extract_project_path("camel [/home/user/camel] – main.py").as_deref(),
+         Some("/home/user/camel")
+      );
+      let home = expand_home(Path::new("~/projects/camel"));
+      assert_eq!(
+         extract_project_path("camel [~/projects/camel] – main.py").map(PathBuf::from),
+         Some(home)
+      );
+   }
+
+   #[test]
+   fn skips_brackets_in_file_names() {
+      assert_eq!(
+         extract_project_path("camel [/home/user/camel] – main[1].py").as_deref(),
+         Some("/home/user/camel")
+      );
+      assert_eq!(
+         extract_project_path("camel [/home/user/camel-v2]").as_deref(),
+         Some("/home/user/camel-v2")
+      );
+   }
+
+   #[test]
+   fn needs_a_path_in_the_title() {
+      assert_eq!(extract_project_path("camel – main.py"), None);
+      assert_eq!(extract_project_path("camel – main[1].py"), None);
+      assert_eq!(extract_project_path("camel [/home/user/camel"), None);
+   }
+
+   #[test]
+   fn finds_project_name() {
+      assert_eq!(project_name("camel – main.py"), Some("camel"));
+      assert_eq!(project_name("camel - main.py"), Some("camel"));
+      assert_eq!(
+         project_name("camel [~/projects/camel] – main.py"),
+         Some("camel")
+      );
+      assert_eq!(project_name("camel"), Some("camel"));
+      assert_eq!(project_name(" – main.py"), None);
+   }
+}
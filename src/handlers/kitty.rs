@@ -0,0 +1,467 @@
+use std::sync::LazyLock;
+
+use log::debug;
+use regex::Regex;
+use serde::Deserialize;
+
+use super::{
+   AppHandler,
+   Launch,
+};
+use crate::{
+   get_local_hostname,
+   launch::WaitFor,
+   pattern,
+   shell,
+   ssh::{
+      self,
+      SshConfig,
+   },
+};
+
+/// The title format of oh-my-tmux: `hostname ❐ session_name ● window_index
+/// program_name`
+static DEFAULT_TITLE: LazyLock<Regex> = LazyLock::new(|| {
+   Regex::new(r"^\s*(?<hostname>[^❐●]*[^❐●\s])\s*❐\s*(?<session>[^●]*[^●\s])\s*●")
+      .expect("default tmux title regex is valid")
+});
+
+/// How often to check for a local session, half a second apart
+const DEFAULT_WAIT_RETRIES: u32 = 30;
+
+/// Seconds between two checks for a session
+const RETRY_INTERVAL: f64 = 0.5;
+
+/// How often to check for a remote session, half a second apart
+const DEFAULT_REMOTE_WAIT_RETRIES: u32 = 20;
+
+/// How often to retry connecting to a remote host, two seconds apart
+const DEFAULT_SSH_RETRIES: u32 = 10;
+
+/// Stands for the session name in the configured attach command
+const SESSION_PLACEHOLDER: &str = "{session}";
+
+const DEFAULT_ATTACH: &str = "tmux attach -t {session}";
+
+const DEFAULT_NEW_SESSION: &str = "tmux new-session \\; choose-tree -s";
+
+/// The `[tmux]` section of the config, describing how kitty windows showing
+/// tmux are recognized and reattached
+#[derive(Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TmuxConfig {
+   /// Regex matching the title of a kitty window showing tmux, with
+   /// `hostname` and `session` capture groups
+   #[serde(default, deserialize_with = "pattern::deserialize_regex")]
+   title:               Option<Regex>,
+   /// How often to check whether a local session exists yet
+   #[serde(default)]
+   wait_retries:        Option<u32>,
+   /// How often to check whether a remote session exists yet
+   #[serde(default)]
+   remote_wait_retries: Option<u32>,
+   /// How often to retry connecting to a remote host
+   #[serde(default)]
+   ssh_retries:         Option<u32>,
+   /// Command attaching to the session, with `{session}` replaced by its
+   /// quoted name
+   #[serde(default)]
+   attach:              Option<String>,
+   /// Command run when the session can't be attached to, with `{session}`
+   /// replaced the same way
+   #[serde(default)]
+   new_session:         Option<String>,
+}
+
+impl TmuxConfig {
+   /// The configured command attaching to `session`
+   pub fn attach(&self, session: &str) -> String {
+      self
+         .attach
+         .as_deref()
+         .unwrap_or(DEFAULT_ATTACH)
+         .replace(SESSION_PLACEHOLDER, &shell_words::quote(session))
+   }
+
+   /// The configured command run when `session` can't be attached to, if any
+   pub fn new_session(&self, session: &str) -> Option<String> {
+      let new_session = self.new_session.as_deref()?;
+      Some(new_session.replace(SESSION_PLACEHOLDER, &shell_words::quote(session)))
+   }
+
+   /// How long to wait for a local session to exist, in seconds
+   pub fn wait_timeout(&self) -> f64 {
+      f64::from(self.wait_retries.unwrap_or(DEFAULT_WAIT_RETRIES)) * RETRY_INTERVAL
+   }
+}
+
+/// Kitty terminal with a tmux session (local or remote via SSH), reattached to
+/// the session named in the window title
+pub struct Kitty {
+   pub tmux: TmuxConfig,
+   pub ssh:  SshConfig,
+}
+
+impl AppHandler for Kitty {
+   fn name(&self) -> &'static str {
+      "kitty"
+   }
+
+   fn matches(&self, launch: &Launch) -> bool {
+      launch.app_id == "kitty"
+   }
+
+   fn build_command(&self, launch: &Launch) -> Vec<String> {
+      let Some((tmux_info, is_local)) = self.session(launch) else {
+         // No tmux session detected, just launch kitty normally
+         return launch.command.clone();
+      };
+
+      // The session name comes straight from the window title, so it is quoted
+      // everywhere it ends up in a script
+      let session = shell_words::quote(&tmux_info.session);
+      let attach = self.tmux.attach(&tmux_info.session);
+      let new_session = self
+         .tmux
+         .new_session(&tmux_info.session)
+         .unwrap_or_else(|| DEFAULT_NEW_SESSION.to_owned());
+
+      if is_local {
+         // Local tmux session
+         // tmux server is started at niri startup, tmux-continuum auto-restores sessions.
+         // The restore waits for the session to appear (see `wait_for`), so just attach.
+         debug!("found local tmux session: {}", tmux_info.session);
+         let mut command = launch.command.clone();
+         command.extend([
+            "-e".to_owned(),
+            "sh".to_owned(),
+            "-c".to_owned(),
+            format!("{attach} || {new_session}"),
+         ]);
+         command
+      } else {
+         // Remote tmux session via SSH
+         // Retry SSH connection if DNS/network not ready yet
+         debug!(
+            "found remote tmux session: {} on host {}",
+            tmux_info.session, tmux_info.hostname
+         );
+         // The remote script is run by the remote user's shell, which gets it
+         // as a single quoted argument to ssh
+         let remote_script = format!(
+            "for i in $(seq 1 {wait_retries}); do tmux has-session -t {session} 2>/dev/null && break; sleep 0.5; done; \
+             {attach} || {new_session}",
+            wait_retries = self
+               .tmux
+               .remote_wait_retries
+               .unwrap_or(DEFAULT_REMOTE_WAIT_RETRIES),
+         );
+         let mut command = launch.command.clone();
+         command.extend([
+            "-e".to_owned(),
+            "sh".to_owned(),
+            "-c".to_owned(),
+            format!(
+               "for retry in $(seq 1 {ssh_retries}); do \
+                  ssh -t {hostname} {remote_script} && exit 0; \
+                  echo \"SSH failed, retrying in 2s... (attempt $retry/{ssh_retries})\"; \
+                  sleep 2; \
+                done; \
+                echo \"SSH failed after {ssh_retries} attempts\"; read -p \"Press Enter to close...\"",
+               hostname = shell_words::quote(&self.ssh.resolve(&tmux_info.hostname)),
+               remote_script = shell_words::quote(&remote_script),
+               ssh_retries = self.tmux.ssh_retries.unwrap_or(DEFAULT_SSH_RETRIES),
+            ),
+         ]);
+         command
+      }
+   }
+
+   fn wait_for(&self, launch: &Launch) -> Option<WaitFor> {
+      // Remote sessions are waited for on the remote host, by the SSH script
+      let (tmux_info, true) = self.session(launch)? else {
+         return None;
+      };
+      Some(WaitFor {
+         command: Some(format!(
+            "tmux has-session -t {}",
+            shell_words::quote(&tmux_info.session)
+         )),
+         timeout: Some(self.tmux.wait_timeout()),
+         ..WaitFor::default()
+      })
+   }
+}
+
+impl Kitty {
+   /// The tmux session named in the window title, and whether it runs on this
+   /// host
+   fn session(&self, launch: &Launch) -> Option<(TmuxInfo, bool)> {
+      // Window title format: "hostname ❐ session_name ● window_index program_name"
+      //
+      // Examples:
+      // - Local:  "YuzhouArch ❐ main ● 1 zsh"     -> tmux attach -t main
+      // - Remote: "surfi1 ❐ dt-agent ● 2 zsh"    -> ssh surfi1 -t tmux attach -t dt-agent
+      let title_format = self.tmux.title.as_ref().unwrap_or(&DEFAULT_TITLE);
+      let tmux_info = extract_tmux_info(launch.title?, title_format)?;
+      let is_local = get_local_hostname()
+         .is_some_and(|hostname| ssh::hostnames_match(&hostname, &tmux_info.hostname));
+      Some((tmux_info, is_local))
+   }
+}
+
+/// Information extracted from a tmux window title
+struct TmuxInfo {
+   /// The hostname where tmux is running (could be local or remote)
+   hostname: String,
+   /// The tmux session name
+   session: String,
+}
+
+/// Extract hostname and tmux session name from kitty window title.
+///
+/// When running tmux inside kitty, the window title follows the format set by oh-my-tmux
+/// unless configured otherwise:
+/// `hostname ❐ session_name ● window_index program_name`
+///
+/// For example:
+/// - Local:  "YuzhouArch ❐ main ● 1 claude"
+/// - Remote: "surfi1 ❐ dt-agent ● 2 zsh"
+///
+/// The `❐` (U+2750) marks the boundary between hostname and session name.
+/// The `●` (U+25CF) marks the end of session name.
+///
+/// # Arguments
+/// * `title` - The window title from niri IPC
+/// * `format` - Regex with `hostname` and `session` capture groups
+///
+/// # Returns
+/// * `Some(TmuxInfo)` - The hostname and tmux session name
+/// * `None` - If the title doesn't match the expected format
+///
+/// # Example
+/// ```
+/// // Input:  "surfi1 ❐ dt-agent ● 2 zsh"
+/// // Output: Some(TmuxInfo { hostname: "surfi1", session: "dt-agent" })
+/// ```
+fn extract_tmux_info(title: &str, format: &Regex) -> Option<TmuxInfo> {
+   let captures = format.captures(title)?;
+   let hostname = captures.name("hostname")?.as_str().trim();
+   let session = captures.name("session")?.as_str().trim();
+   if !shell::is_valid_hostname(hostname) || !shell::is_valid_session_name(session) {
+      debug!("ignoring tmux session {session:?} on host {hostname:?} with an unusual name");
+      return None;
+   }
+
+   Some(TmuxInfo {
+      hostname: hostname.to_owned(),
+      session:  session.to_owned(),
+   })
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   fn kitty() -> Kitty {
+      Kitty {
+         tmux: TmuxConfig::default(),
+         ssh:  SshConfig::default(),
+      }
+   }
+
+   fn launch(title: &str) -> Launch<'_> {
+      Launch {
+         app_id:      "kitty",
+         title:       Some(title),
+         command:     vec!["kitty".to_owned()],
+         env:         None,
+         cwd:         None,
+         exact:       false,
+         multiplexer: None,
+         cmdline:     None,
+      }
+   }
+
+   /// The script kitty is told to run, split into words the way `sh` would
+   fn script_words(command: &[String]) -> Vec<String> {
+      let [ref kitty, ref flag, ref shell, ref dash_c, ref script] = *command else {
+         panic!("unexpected command {command:?}");
+      };
+      assert_eq!([kitty, flag, shell, dash_c], ["kitty", "-e", "sh", "-c"]);
+      shell_words::split(script).unwrap()
+   }
+
+   fn has_words(words: &[String], expected: &[&str]) -> bool {
+      words
+         .windows(expected.len())
+         .any(|window| window == expected)
+   }
+
+   /// The remote script passed to `ssh` in a generated command
+   fn remote_script(words: &[String], hostname: &str) -> Vec<String> {
+      let start = words
+         .windows(3)
+         .position(|window| window == ["ssh", "-t", hostname])
+         .unwrap();
+      shell_words::split(&words[start + 3]).unwrap()
+   }
+
+   fn assert_local_session(session: &str) {
+      let Some(hostname) = get_local_hostname() else {
+         return;
+      };
+      let command = kitty().build_command(&launch(&format!("{hostname} ❐ {session} ● 1 zsh")));
+      let words = script_words(&command);
+      assert!(
+         has_words(&words, &["tmux", "attach", "-t", session]),
+         "{words:?}"
+      );
+   }
+
+   fn assert_remote_session(session: &str) {
+      let command = kitty().build_command(&launch(&format!("remote-host ❐ {session} ● 1 zsh")));
+      let remote = remote_script(&script_words(&command), "remote-host");
+      assert!(
+         has_words(&remote, &["tmux", "has-session", "-t", session]),
+         "{remote:?}"
+      );
+      assert!(
+         has_words(&remote, &["tmux", "attach", "-t", session]),
+         "{remote:?}"
+      );
+   }
+
+   #[test]
+   fn extracts_default_title() {
+      let info = extract_tmux_info("surfi1 ❐ dt-agent ● 2 zsh", &DEFAULT_TITLE).unwrap();
+      assert_eq!(info.hostname, "surfi1");
+      assert_eq!(info.session, "dt-agent");
+   }
+
+   #[test]
+   fn quotes_session_with_shell_syntax() {
+      let session = "x; rm -rf ~";
+      assert_eq!(
+         extract_tmux_info(&format!("host ❐ {session} ●"), &DEFAULT_TITLE)
+            .unwrap()
+            .session,
+         session
+      );
+      assert_local_session(session);
+      assert_remote_session(session);
+   }
+
+   #[test]
+   fn quotes_session_with_substitutions() {
+      for session in ["$(reboot)", "`reboot`", "a'$(reboot)'b"] {
+         assert_local_session(session);
+         assert_remote_session(session);
+      }
+   }
+
+   #[test]
+   fn ignores_hostname_starting_with_dash() {
+      let title = "-oProxyCommand=reboot ❐ main ● 1 zsh";
+      assert!(extract_tmux_info(title, &DEFAULT_TITLE).is_none());
+      assert_eq!(kitty().build_command(&launch(title)), ["kitty"]);
+   }
+
+   #[test]
+   fn ignores_hostname_with_semicolon() {
+      let title = "host;reboot ❐ main ● 1 zsh";
+      assert!(extract_tmux_info(title, &DEFAULT_TITLE).is_none());
+      assert_eq!(kitty().build_command(&launch(title)), ["kitty"]);
+   }
+
+   #[test]
+   fn ignores_control_characters() {
+      for title in [
+         "host ❐ ma\x1b]0;in ● 1 zsh",
+         "host ❐ ma\nin ● 1 zsh",
+         "ho\x07st ❐ main ●",
+      ] {
+         assert!(
+            extract_tmux_info(title, &DEFAULT_TITLE).is_none(),
+            "{title:?}"
+         );
+         assert_eq!(kitty().build_command(&launch(title)), ["kitty"]);
+      }
+   }
+
+   #[test]
+   fn honours_custom_title() {
+      let format = Regex::new(r"^\[(?<hostname>[^:]+):(?<session>[^\]]+)\]").unwrap();
+      let info = extract_tmux_info("[build-box:deploy] vim", &format).unwrap();
+      assert_eq!(info.hostname, "build-box");
+      assert_eq!(info.session, "deploy");
+
+      let kitty = Kitty {
+         tmux: TmuxConfig {
+            title: Some(format),
+            ..TmuxConfig::default()
+         },
+         ..kitty()
+      };
+      let command = kitty.build_command(&launch("[build-box:deploy] vim"));
+      let remote = remote_script(&script_words(&command), "build-box");
+      assert!(
+         has_words(&remote, &["tmux", "attach", "-t", "deploy"]),
+         "{remote:?}"
+      );
+      // The default format no longer applies
+      assert_eq!(kitty.build_command(&launch("host ❐ main ● 1 zsh")), [
+         "kitty"
+      ]);
+   }
+
+   #[test]
+   fn ignores_title_format_without_group() {
+      let format = Regex::new(r"^(?<hostname>\S+) ❐").unwrap();
+      assert!(extract_tmux_info("host ❐ main ● 1 zsh", &format).is_none());
+   }
+
+   #[test]
+   fn substitutes_quoted_session_in_attach() {
+      let kitty = Kitty {
+         tmux: TmuxConfig {
+            attach: Some("tmux -L work attach -d -t {session}".to_owned()),
+            ..TmuxConfig::default()
+         },
+         ..kitty()
+      };
+      let command = kitty.build_command(&launch("remote-host ❐ x; reboot ● 1 zsh"));
+      let remote = remote_script(&script_words(&command), "remote-host");
+      assert!(
+         has_words(&remote, &[
+            "tmux",
+            "-L",
+            "work",
+            "attach",
+            "-d",
+            "-t",
+            "x; reboot"
+         ]),
+         "{remote:?}"
+      );
+      assert!(command[4].contains("'x; reboot'"), "{command:?}");
+   }
+
+   #[test]
+   fn uses_configured_retries() {
+      let kitty = Kitty {
+         tmux: TmuxConfig {
+            remote_wait_retries: Some(7),
+            ssh_retries: Some(3),
+            ..TmuxConfig::default()
+         },
+         ..kitty()
+      };
+      let command = kitty.build_command(&launch("remote-host ❐ main ● 1 zsh"));
+      let words = script_words(&command);
+      assert!(has_words(&words, &["$(seq", "1", "3);"]), "{words:?}");
+      assert!(command[4].contains("after 3 attempts"), "{command:?}");
+      let remote = remote_script(&words, "remote-host");
+      assert!(has_words(&remote, &["$(seq", "1", "7);"]), "{remote:?}");
+   }
+}
@@ -0,0 +1,213 @@
+use std::{
+   collections::HashMap,
+   path::PathBuf,
+};
+
+use log::debug;
+use niri_ipc::{
+   Window,
+   socket::Socket,
+};
+
+use crate::{
+   Config,
+   launch::WaitFor,
+   multiplexer::Multiplexer,
+};
+
+mod chromium;
+mod edge;
+mod firefox;
+mod jetbrains;
+mod kitty;
+mod neovim;
+mod rule;
+mod terminal;
+mod vscode;
+mod zed;
+
+pub use kitty::TmuxConfig;
+pub use rule::LaunchRule;
+
+/// A saved window that is about to be relaunched
+pub struct Launch<'session> {
+   /// The application id of the saved window
+   pub app_id:      &'session str,
+   /// The title of the saved window
+   pub title:       Option<&'session str>,
+   /// The configured command for the app split into arguments, see
+   /// `[launch]` in the config
+   pub command:     Vec<String>,
+   /// Environment variables to set for the app
+   pub env:         Option<&'session HashMap<String, String>>,
+   /// Directory to start the app in
+   pub cwd:         Option<PathBuf>,
+   /// Whether `command` is the exact command line recorded at save time, which
+   /// is run as is
+   pub exact:       bool,
+   /// Multiplexer session a terminal window was attached to
+   pub multiplexer: Option<&'session Multiplexer>,
+   /// Command line the window's process was running with when the session
+   /// was saved
+   pub cmdline:     Option<&'session [String]>,
+}
+
+/// Knows how to relaunch the windows of an application
+pub trait AppHandler: Sync {
+   /// Name of the handler, for logs
+   fn name(&self) -> &'static str;
+
+   /// Whether this handler is responsible for relaunching the window
+   fn matches(&self, launch: &Launch) -> bool;
+
+   /// The command that relaunches the window
+   fn build_command(&self, launch: &Launch) -> Vec<String>;
+
+   /// A condition to wait for before relaunching the window, unless the
+   /// config sets one with `wait_for`
+   fn wait_for(&self, _launch: &Launch) -> Option<WaitFor> {
+      None
+   }
+
+   /// Called once the relaunched window has been placed where it was in the
+   /// session
+   fn after_place(&self, _socket: &mut Socket, _launch: &Launch, _window: &Window) {}
+}
+
+/// Launches the configured command as is
+struct Generic;
+
+impl AppHandler for Generic {
+   fn name(&self) -> &'static str {
+      "generic"
+   }
+
+   fn matches(&self, _launch: &Launch) -> bool {
+      true
+   }
+
+   fn build_command(&self, launch: &Launch) -> Vec<String> {
+      launch.command.clone()
+   }
+}
+
+/// The app handlers, in the order they are tried
+pub struct Registry {
+   handlers: Vec<Box<dyn AppHandler>>,
+}
+
+impl Registry {
+   /// A registry with the rules from the config, followed by the handlers
+   /// that ship with nirinit
+   pub fn new(config: &Config) -> Self {
+      let mut handlers = config
+         .rules
+         .iter()
+         .map(|rule| Box::new(rule.clone()) as Box<dyn AppHandler>)
+         .collect::<Vec<_>>();
+      handlers.push(Box::new(terminal::Terminal {
+         tmux: config.tmux.clone(),
+      }));
+      handlers.push(Box::new(jetbrains::JetBrains));
+      handlers.push(Box::new(vscode::VsCode));
+      handlers.push(Box::new(zed::Zed));
+      handlers.push(Box::new(neovim::Neovim));
+      handlers.push(Box::new(edge::Edge));
+      handlers.push(Box::new(chromium::Chromium));
+      handlers.push(Box::new(firefox::Firefox));
+      handlers.push(Box::new(kitty::Kitty {
+         tmux: config.tmux.clone(),
+         ssh:  config.ssh.clone(),
+      }));
+      Self { handlers }
+   }
+
+   /// The first handler responsible for the window, falling back to launching
+   /// the configured command as is
+   pub fn handler_for(&self, launch: &Launch) -> &dyn AppHandler {
+      if launch.exact {
+         return &Generic;
+      }
+      self
+         .handlers
+         .iter()
+         .map(Box::as_ref)
+         .find(|handler| handler.matches(launch))
+         .unwrap_or(&Generic)
+   }
+
+   /// The command that relaunches the window, along with the handler that
+   /// built it
+   pub fn build_command(&self, launch: &Launch) -> (&dyn AppHandler, Vec<String>) {
+      let handler = self.handler_for(launch);
+      debug!("relaunching {} with the {} handler", launch.app_id, handler.name());
+      (handler, handler.build_command(launch))
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use crate::multiplexer::MultiplexerKind;
+
+   fn registry(config: &str) -> Registry {
+      Registry::new(&toml::from_str::<Config>(config).unwrap())
+   }
+
+   fn kitty_launch(multiplexer: &Multiplexer) -> Launch<'_> {
+      Launch {
+         app_id:      "kitty",
+         title:       Some("remote-host ❐ main ● 1 zsh"),
+         command:     vec!["kitty".to_owned()],
+         env:         None,
+         cwd:         None,
+         exact:       false,
+         multiplexer: Some(multiplexer),
+         cmdline:     None,
+      }
+   }
+
+   #[test]
+   fn reattaches_tmux_the_configured_way() {
+      let registry = registry(
+         r#"
+         [tmux]
+         attach = "tmux -L work attach -t {session}"
+         new_session = "tmux -L work new-session -s {session}"
+         wait_retries = 4
+         "#,
+      );
+      let multiplexer = Multiplexer {
+         kind:    MultiplexerKind::Tmux,
+         session: Some("it's".to_owned()),
+      };
+      let launch = kitty_launch(&multiplexer);
+
+      let (handler, command) = registry.build_command(&launch);
+      assert_eq!(command, [
+         "kitty",
+         "-e",
+         "sh",
+         "-c",
+         r"tmux -L work attach -t 'it'\''s' || tmux -L work new-session -s 'it'\''s'",
+      ]);
+      let wait_for = handler.wait_for(&launch).unwrap();
+      assert_eq!(wait_for.timeout, Some(2.0));
+   }
+
+   #[test]
+   fn reattaches_tmux_by_default() {
+      let multiplexer = Multiplexer {
+         kind:    MultiplexerKind::Tmux,
+         session: Some("main".to_owned()),
+      };
+      let (_, command) = registry("").build_command(&kitty_launch(&multiplexer));
+      assert_eq!(command, [
+         "kitty",
+         "-e",
+         "sh",
+         "-c",
+         "tmux attach -t main || tmux new-session -s main",
+      ]);
+   }
+}
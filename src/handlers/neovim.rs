@@ -0,0 +1,79 @@
+use std::{
+   path::{
+      Path,
+      PathBuf,
+   },
+   sync::LazyLock,
+};
+
+use log::debug;
+use regex::Regex;
+
+use super::{
+   AppHandler,
+   Launch,
+};
+use crate::launch::expand_home;
+
+/// App ids of Neovim GUIs
+const GUIS: &[&str] = &["neovide", "nvim-qt", "gnvim"];
+
+/// Neovim's default `titlestring`: `file [+-] (directory) [(2 of 5)] - NVIM`
+static TITLE: LazyLock<Regex> = LazyLock::new(|| {
+   Regex::new(r"^(?<file>.+?)(?: [-+=]+)? \((?<directory>[^()]+)\)(?: \(\d+ of \d+\))? - N?VIM$")
+      .expect("neovim title regex is valid")
+});
+
+/// Neovide and other Neovim GUIs, reopened with the file from the window
+/// title, or the files they were started with
+pub struct Neovim;
+
+impl AppHandler for Neovim {
+   fn name(&self) -> &'static str {
+      "neovim"
+   }
+
+   fn matches(&self, launch: &Launch) -> bool {
+      GUIS.contains(&launch.app_id)
+   }
+
+   fn build_command(&self, launch: &Launch) -> Vec<String> {
+      let mut command = launch.command.clone();
+      // The title only has the file when Neovim's `title` option is set,
+      // otherwise the GUI shows its own name
+      let paths = launch
+         .title
+         .and_then(title_path)
+         .map(|path| vec![path])
+         .or_else(|| {
+            let paths = launch
+               .cmdline?
+               .iter()
+               .skip(1)
+               .filter(|arg| !arg.starts_with('-'))
+               .map(PathBuf::from)
+               .filter(|path| path.exists())
+               .collect::<Vec<_>>();
+            (!paths.is_empty()).then_some(paths)
+         });
+
+      if let Some(paths) = paths {
+         debug!("reopening {} with {paths:?}", launch.app_id);
+         command.extend(
+            paths
+               .into_iter()
+               .filter_map(|path| path.into_os_string().into_string().ok()),
+         );
+      }
+      command
+   }
+}
+
+/// The file in a window title like "main.rs + (~/projects/nirinit/src) - NVIM",
+/// or its directory if the file no longer exists
+fn title_path(title: &str) -> Option<PathBuf> {
+   let captures = TITLE.captures(title)?;
+   let directory = expand_home(Path::new(captures.name("directory")?.as_str()));
+   let file = directory.join(captures.name("file")?.as_str());
+   [file, directory].into_iter().find(|path| path.exists())
+}
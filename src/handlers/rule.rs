@@ -0,0 +1,71 @@
+use log::debug;
+use regex::Regex;
+use serde::Deserialize;
+
+use super::{
+   AppHandler,
+   Launch,
+};
+use crate::{
+   launch,
+   pattern::{
+      self,
+      Pattern,
+   },
+};
+
+/// A `[[rule]]` from the config, relaunching matching windows with a command
+/// built from their title
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LaunchRule {
+   /// The app ids the rule applies to, as a glob or `/regex/`
+   app_id:  Pattern,
+   /// Regex the title has to match. Its capture groups can be used in the
+   /// command as `$1` or `${name}`.
+   #[serde(default, deserialize_with = "pattern::deserialize_regex")]
+   title:   Option<Regex>,
+   /// The command to launch, e.g. `kitty nvim $1`
+   command: String,
+}
+
+impl AppHandler for LaunchRule {
+   fn name(&self) -> &'static str {
+      "rule"
+   }
+
+   fn matches(&self, launch: &Launch) -> bool {
+      self.app_id.is_match(launch.app_id)
+         && self.title.as_ref().is_none_or(|title| {
+            launch
+               .title
+               .is_some_and(|window_title| title.is_match(window_title))
+         })
+   }
+
+   fn build_command(&self, launch: &Launch) -> Vec<String> {
+      debug!("app_id {} matched rule for {}", launch.app_id, self.app_id);
+
+      let captures = self
+         .title
+         .as_ref()
+         .zip(launch.title)
+         .and_then(|(title, window_title)| title.captures(window_title));
+
+      // Captures are substituted per argument, so a captured path with spaces
+      // stays a single argument
+      launch::split_command_line(&self.command)
+         .into_iter()
+         .map(|word| {
+            captures.as_ref().map_or_else(
+               || word.clone(),
+               |captures| {
+                  let mut expanded = String::new();
+                  captures.expand(&word, &mut expanded);
+                  expanded
+               },
+            )
+         })
+         .collect()
+   }
+}
@@ -0,0 +1,144 @@
+use log::debug;
+
+use super::{
+   AppHandler,
+   Launch,
+   TmuxConfig,
+};
+use crate::{
+   launch::WaitFor,
+   multiplexer::{
+      Multiplexer,
+      MultiplexerKind,
+   },
+};
+
+/// Terminal emulators by app id, with the arguments that make them run a
+/// command instead of the user's shell
+const TERMINALS: &[(&str, &[&str])] = &[
+   ("kitty", &["-e"]),
+   ("foot", &[]),
+   ("Alacritty", &["-e"]),
+   ("org.wezfurlong.wezterm", &["start", "--"]),
+   ("com.mitchellh.ghostty", &["-e"]),
+];
+
+/// Terminal emulators that were attached to a tmux, zellij or screen session,
+/// reattached to that session. tmux sessions are reattached the way `[tmux]`
+/// in the config says.
+pub struct Terminal {
+   pub tmux: TmuxConfig,
+}
+
+impl AppHandler for Terminal {
+   fn name(&self) -> &'static str {
+      "terminal"
+   }
+
+   fn matches(&self, launch: &Launch) -> bool {
+      launch.multiplexer.is_some_and(Multiplexer::can_reattach)
+         && exec_args(launch.app_id).is_some()
+   }
+
+   fn build_command(&self, launch: &Launch) -> Vec<String> {
+      let (Some(multiplexer), Some(exec_args)) = (launch.multiplexer, exec_args(launch.app_id))
+      else {
+         return launch.command.clone();
+      };
+
+      let script = match (multiplexer.kind, multiplexer.session.as_deref()) {
+         (MultiplexerKind::Tmux, Some(session)) => {
+            format!(
+               "{} || {}",
+               self.tmux.attach(session),
+               self.tmux.new_session(session).unwrap_or_else(|| {
+                  format!("tmux new-session -s {}", shell_words::quote(session))
+               })
+            )
+         },
+         _ => multiplexer.attach_script(),
+      };
+      debug!("reattaching {} with `{script}`", launch.app_id);
+
+      let mut command = launch.command.clone();
+      command.extend(exec_args.iter().map(|&arg| arg.to_owned()));
+      command.extend(["sh".to_owned(), "-c".to_owned(), script]);
+      command
+   }
+
+   fn wait_for(&self, launch: &Launch) -> Option<WaitFor> {
+      let command = launch.multiplexer?.session_check()?;
+      Some(WaitFor {
+         command: Some(command),
+         timeout: Some(self.tmux.wait_timeout()),
+         ..WaitFor::default()
+      })
+   }
+}
+
+fn exec_args(app_id: &str) -> Option<&'static [&'static str]> {
+   TERMINALS
+      .iter()
+      .find(|&&(terminal, _)| terminal == app_id)
+      .map(|&(_, args)| args)
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   fn terminal() -> Terminal {
+      Terminal {
+         tmux: TmuxConfig::default(),
+      }
+   }
+
+   fn launch<'session>(
+      app_id: &'session str,
+      multiplexer: &'session Multiplexer,
+   ) -> Launch<'session> {
+      Launch {
+         app_id,
+         title: Some("host ❐ main ● 1 zsh"),
+         command: vec![app_id.to_owned()],
+         env: None,
+         cwd: None,
+         exact: false,
+         multiplexer: Some(multiplexer),
+         cmdline: None,
+      }
+   }
+
+   #[test]
+   fn leaves_unnamed_tmux_sessions_to_other_handlers() {
+      let multiplexer = Multiplexer {
+         kind:    MultiplexerKind::Tmux,
+         session: None,
+      };
+      assert!(!terminal().matches(&launch("kitty", &multiplexer)));
+   }
+
+   #[test]
+   fn reattaches_named_sessions() {
+      let multiplexer = Multiplexer {
+         kind:    MultiplexerKind::Tmux,
+         session: Some("main".to_owned()),
+      };
+      assert!(terminal().matches(&launch("foot", &multiplexer)));
+      assert!(!terminal().matches(&launch("org.gnome.Nautilus", &multiplexer)));
+   }
+
+   #[test]
+   fn reattaches_unnamed_screen_sessions() {
+      let multiplexer = Multiplexer {
+         kind:    MultiplexerKind::Screen,
+         session: None,
+      };
+      assert_eq!(terminal().build_command(&launch("foot", &multiplexer)), [
+         "foot",
+         "sh",
+         "-c",
+         "screen -D -R"
+      ]);
+   }
+}
@@ -0,0 +1,220 @@
+use std::{
+   ffi::OsString,
+   fs,
+   os::unix::ffi::OsStringExt as _,
+   path::{
+      Path,
+      PathBuf,
+   },
+};
+
+use log::debug;
+use serde_json::Value;
+
+use super::{
+   AppHandler,
+   Launch,
+};
+
+/// Builds of VS Code by app id, with their config directory and the app name
+/// at the end of their window titles
+const EDITORS: &[(&str, &str, &str)] = &[
+   ("code", "Code", "Visual Studio Code"),
+   ("code-url-handler", "Code", "Visual Studio Code"),
+   (
+      "code-insiders",
+      "Code - Insiders",
+      "Visual Studio Code - Insiders",
+   ),
+   ("code-oss", "Code - OSS", "Code - OSS"),
+   ("codium", "VSCodium", "VSCodium"),
+   ("codium-url-handler", "VSCodium", "VSCodium"),
+   ("VSCodium", "VSCodium", "VSCodium"),
+];
+
+/// Marks a multi-root workspace in the window title
+const WORKSPACE_SUFFIX: &str = " (Workspace)";
+
+/// VS Code and VSCodium, reopened with the folder or workspace from the window
+/// title
+pub struct VsCode;
+
+impl AppHandler for VsCode {
+   fn name(&self) -> &'static str {
+      "vscode"
+   }
+
+   fn matches(&self, launch: &Launch) -> bool {
+      editor(launch.app_id).is_some()
+   }
+
+   fn build_command(&self, launch: &Launch) -> Vec<String> {
+      let mut command = launch.command.clone();
+      let Some((config_dir, app_name)) = editor(launch.app_id) else {
+         return command;
+      };
+
+      // Window title format: "file - folder - Visual Studio Code". The title
+      // only has the name of the folder, so its path is looked up in the
+      // folders and workspaces VS Code remembers.
+      let folder = launch.title.and_then(|title| {
+         let names = title_names(title, app_name)?;
+         let known = known_folders(config_dir);
+         // The folder is the last part, unless a profile name follows it
+         names.iter().rev().find_map(|name| {
+            known
+               .iter()
+               .find(|folder| folder.has_name(name))
+               .map(|folder| folder.path.clone())
+         })
+      });
+      match folder
+         .map(PathBuf::into_os_string)
+         .map(OsString::into_string)
+      {
+         Some(Ok(folder)) => {
+            debug!("reopening {} with {folder}", launch.app_id);
+            command.push(folder);
+         },
+         _ => debug!("no folder found for {} window", launch.app_id),
+      }
+      command
+   }
+}
+
+/// A folder or workspace file VS Code remembers
+struct KnownFolder {
+   path:      PathBuf,
+   /// Whether `path` is a `.code-workspace` file rather than a folder
+   workspace: bool,
+}
+
+impl KnownFolder {
+   /// Whether the window title shows this folder as `name`, which is
+   /// `folder` for a folder and `name (Workspace)` for `name.code-workspace`
+   fn has_name(&self, name: &str) -> bool {
+      if self.workspace {
+         name
+            .strip_suffix(WORKSPACE_SUFFIX)
+            .is_some_and(|name| self.path.file_stem().is_some_and(|stem| stem == name))
+      } else {
+         self
+            .path
+            .file_name()
+            .is_some_and(|file_name| file_name == name)
+      }
+   }
+}
+
+fn editor(app_id: &str) -> Option<(&'static str, &'static str)> {
+   EDITORS
+      .iter()
+      .find(|&&(id, ..)| id == app_id)
+      .map(|&(_, config_dir, app_name)| (config_dir, app_name))
+}
+
+/// The parts of a window title before the app name, e.g. `main.rs` and
+/// `nirinit` in "● main.rs - nirinit - Visual Studio Code"
+fn title_names<'title>(title: &'title str, app_name: &str) -> Option<Vec<&'title str>> {
+   // `●` marks unsaved changes
+   let title = title.trim_start_matches('●').trim_start();
+   let names = title.strip_suffix(app_name)?.strip_suffix(" - ")?;
+   Some(names.split(" - ").collect())
+}
+
+/// The folders and workspaces from VS Code's `storage.json`, the ones open in
+/// a window first.
+///
+/// The file lives at `~/.config/Code/User/globalStorage/storage.json`, or at
+/// `~/.config/Code/storage.json` in older versions, and looks like
+///
+/// ```json
+/// {
+///   "windowsState": {
+///     "lastActiveWindow": { "folder": "file:///home/user/projects/nirinit" },
+///     "openedWindows": [{ "workspace": { "configPath": "file:///home/user/work.code-workspace" } }]
+///   },
+///   "profileAssociations": { "workspaces": { "file:///home/user/projects/camel": "__default__profile__" } }
+/// }
+/// ```
+fn known_folders(config_dir: &str) -> Vec<KnownFolder> {
+   let Some(config_dir) = dirs::config_dir().map(|dir| dir.join(config_dir)) else {
+      return Vec::new();
+   };
+   let Some(storage) = [
+      config_dir.join("User/globalStorage/storage.json"),
+      config_dir.join("storage.json"),
+   ]
+   .iter()
+   .find_map(|path| read_json(path)) else {
+      return Vec::new();
+   };
+
+   let windows = storage
+      .pointer("/windowsState/lastActiveWindow")
+      .into_iter()
+      .chain(
+         storage
+            .pointer("/windowsState/openedWindows")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten(),
+      )
+      .flat_map(|window| {
+         [
+            (window.get("folder"), false),
+            (window.pointer("/workspace/configPath"), true),
+         ]
+      });
+   let associated = storage
+      .pointer("/profileAssociations/workspaces")
+      .and_then(Value::as_object)
+      .into_iter()
+      .flat_map(|workspaces| workspaces.keys())
+      .map(|uri| {
+         let workspace = Path::new(uri)
+            .extension()
+            .is_some_and(|extension| extension == "code-workspace");
+         (uri.as_str(), workspace)
+      });
+
+   windows
+      .filter_map(|(uri, workspace)| Some((uri?.as_str()?, workspace)))
+      .chain(associated)
+      .filter_map(|(uri, workspace)| {
+         Some(KnownFolder {
+            path: file_uri_path(uri)?,
+            workspace,
+         })
+      })
+      .filter(|folder| folder.path.exists())
+      .collect()
+}
+
+fn read_json(path: &Path) -> Option<Value> {
+   let content = fs::read_to_string(path).ok()?;
+   serde_json::from_str(&content).ok()
+}
+
+/// The path of a `file://` URI, with percent-escapes decoded. Other URIs,
+/// such as those of remote folders, have no local path.
+fn file_uri_path(uri: &str) -> Option<PathBuf> {
+   let mut parts = uri.strip_prefix("file://")?.split('%');
+   let mut bytes = parts.next().unwrap_or_default().as_bytes().to_vec();
+   for part in parts {
+      let hex = part
+         .get(..2)
+         .filter(|hex| hex.bytes().all(|byte| byte.is_ascii_hexdigit()));
+      match hex.map(|hex| u8::from_str_radix(hex, 16)) {
+         Some(Ok(byte)) => {
+            bytes.push(byte);
+            bytes.extend_from_slice(&part.as_bytes()[2..]);
+         },
+         _ => {
+            bytes.push(b'%');
+            bytes.extend_from_slice(part.as_bytes());
+         },
+      }
+   }
+   Some(PathBuf::from(OsString::from_vec(bytes)))
+}
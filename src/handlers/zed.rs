@@ -0,0 +1,158 @@
+use std::{
+   path::{
+      Path,
+      PathBuf,
+   },
+   process::Command,
+};
+
+use log::debug;
+use serde::Deserialize;
+
+use super::{
+   AppHandler,
+   Launch,
+};
+
+/// Zed's release channels by app id, with the directory of their database
+const CHANNELS: &[(&str, &str)] = &[
+   ("dev.zed.Zed", "0-stable"),
+   ("dev.zed.Zed-Preview", "0-preview"),
+];
+
+/// Workspaces Zed had open, most recent first. `paths` holds the folders of a
+/// workspace, one per line.
+const WORKSPACES_QUERY: &str =
+   "SELECT paths FROM workspaces WHERE paths IS NOT NULL AND paths != '' ORDER BY timestamp DESC";
+
+/// A row of `WORKSPACES_QUERY`, as printed by `sqlite3 -json`
+#[derive(Deserialize)]
+struct WorkspaceRow {
+   paths: String,
+}
+
+/// Zed, reopened with the folders from the window title
+pub struct Zed;
+
+impl AppHandler for Zed {
+   fn name(&self) -> &'static str {
+      "zed"
+   }
+
+   fn matches(&self, launch: &Launch) -> bool {
+      database_dir(launch.app_id).is_some()
+   }
+
+   fn build_command(&self, launch: &Launch) -> Vec<String> {
+      // Window title format: "folder, other_folder — file". The title only has
+      // the names of the folders, so their paths are looked up in the
+      // workspaces Zed remembers, then in the command line it ran with.
+      let mut command = launch.command.clone();
+      let Some(names) = launch.title.and_then(folder_names) else {
+         return command;
+      };
+
+      let folders = recent_workspaces(launch.app_id)
+         .into_iter()
+         .find(|folders| have_names(folders, &names))
+         .or_else(|| {
+            let folders = launch
+               .cmdline?
+               .iter()
+               .skip(1)
+               .map(PathBuf::from)
+               .filter(|path| path.is_dir())
+               .collect::<Vec<_>>();
+            have_names(&folders, &names).then_some(folders)
+         });
+      let Some(folders) = folders else {
+         debug!("no folders found for {} window {names:?}", launch.app_id);
+         return command;
+      };
+
+      debug!("reopening {} with {folders:?}", launch.app_id);
+      command.extend(
+         folders
+            .into_iter()
+            .filter_map(|folder| folder.into_os_string().into_string().ok()),
+      );
+      command
+   }
+}
+
+fn database_dir(app_id: &str) -> Option<&'static str> {
+   CHANNELS
+      .iter()
+      .find(|&&(id, _)| id == app_id)
+      .map(|&(_, dir)| dir)
+}
+
+/// The names of the folders in a window title, e.g. `nirinit` and `niri` in
+/// "nirinit, niri — src/main.rs"
+fn folder_names(title: &str) -> Option<Vec<&str>> {
+   let folders = title.split(" — ").next()?;
+   // `↗` and `↙` mark projects shared with collaborators
+   let folders = folders.trim_end_matches(['↗', '↙']).trim_end();
+   if folders.is_empty() || folders == "empty project" {
+      return None;
+   }
+   Some(folders.split(", ").collect())
+}
+
+/// Whether `folders` are exactly the folders named in a window title
+fn have_names(folders: &[PathBuf], names: &[&str]) -> bool {
+   let mut folder_names = folders
+      .iter()
+      .filter_map(|folder| folder.file_name()?.to_str())
+      .collect::<Vec<_>>();
+   let mut names = names.to_vec();
+   folder_names.sort_unstable();
+   names.sort_unstable();
+   !folders.is_empty() && folder_names == names
+}
+
+/// The folders of the workspaces in Zed's database, most recent first.
+///
+/// Zed keeps its workspaces in a SQLite database at
+/// `~/.local/share/zed/db/0-stable/db.sqlite`, which is read with the
+/// `sqlite3` command line tool. Workspaces whose folders no longer exist are
+/// left out.
+fn recent_workspaces(app_id: &str) -> Vec<Vec<PathBuf>> {
+   let Some(database) = database_dir(app_id)
+      .zip(dirs::data_dir())
+      .map(|(dir, data_dir)| data_dir.join("zed/db").join(dir).join("db.sqlite"))
+      .filter(|database| database.exists())
+   else {
+      return Vec::new();
+   };
+
+   let output = match Command::new("sqlite3")
+      .arg("-readonly")
+      .arg("-json")
+      .arg(&database)
+      .arg(WORKSPACES_QUERY)
+      .output()
+   {
+      Ok(output) if output.status.success() => output,
+      Ok(output) => {
+         debug!(
+            "failed to read workspaces from {}: {}",
+            database.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+         );
+         return Vec::new();
+      },
+      Err(err) => {
+         debug!("failed to run sqlite3: {err}");
+         return Vec::new();
+      },
+   };
+
+   // No rows at all print nothing rather than `[]`
+   let rows = serde_json::from_slice::<Vec<WorkspaceRow>>(&output.stdout).unwrap_or_default();
+   rows
+      .into_iter()
+      .map(|row| row.paths.lines().map(PathBuf::from).collect::<Vec<_>>())
+      .filter(|folders| folders.iter().all(|folder| Path::is_dir(folder)))
+      .collect()
+}
@@ -0,0 +1,291 @@
+use std::{
+   collections::HashMap,
+   path::{
+      Path,
+      PathBuf,
+   },
+   process::{
+      Command,
+      Stdio,
+   },
+   thread,
+   time::{
+      Duration,
+      Instant,
+   },
+};
+
+use log::warn;
+use serde::{
+   Deserialize,
+   Serialize,
+};
+
+use crate::{
+   pattern::Pattern,
+   process,
+};
+
+/// How long to wait for a `wait_for` condition by default
+const DEFAULT_WAIT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long a single run of a `wait_for` command may take before it is killed
+/// and the condition taken not to hold
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How often to look whether a `wait_for` command has exited
+const CHECK_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// How to launch an app, from the `[launch]` table of the config
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum LaunchEntry {
+   /// Just the command
+   Command(LaunchCommand),
+   /// The command along with the environment to run it in
+   Detailed(LaunchOptions),
+}
+
+/// A command, either as a command line or as the program and its arguments
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum LaunchCommand {
+   /// A command line, split into arguments like a POSIX shell would
+   Line(String),
+   /// The program followed by its arguments, passed as is
+   Argv(Vec<String>),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LaunchOptions {
+   /// Command to launch the app with, the app id itself if not given
+   #[serde(default)]
+   command:  Option<LaunchCommand>,
+   /// Environment variables to set for the app
+   #[serde(default)]
+   env:      HashMap<String, String>,
+   /// Directory to start the app in. A leading `~` is expanded to the home
+   /// directory.
+   #[serde(default)]
+   cwd:      Option<PathBuf>,
+   /// App ids, as globs or `/regex/`, whose windows must have appeared before
+   /// the app is launched
+   #[serde(default)]
+   after:    Vec<Pattern>,
+   /// Condition that must hold before the app is launched
+   #[serde(default)]
+   wait_for: Option<WaitFor>,
+   /// How long to wait for the app's window to appear, in seconds
+   #[serde(default)]
+   timeout:  Option<f64>,
+}
+
+/// A condition to wait for before launching an app. Every part that is given
+/// must hold.
+#[derive(Clone, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct WaitFor {
+   /// Shell command that must succeed. It is run every half a second, and
+   /// killed if it takes longer than two seconds.
+   #[serde(default)]
+   pub command: Option<String>,
+   /// File or socket that must exist. A leading `~` is expanded to the home
+   /// directory.
+   #[serde(default)]
+   pub path:    Option<PathBuf>,
+   /// Name of a process that must be running
+   #[serde(default)]
+   pub process: Option<String>,
+   /// How long to wait at most, in seconds, before launching the app anyway
+   #[serde(default)]
+   pub timeout: Option<f64>,
+}
+
+impl LaunchEntry {
+   const fn command(&self) -> Option<&LaunchCommand> {
+      match *self {
+         Self::Command(ref command) => Some(command),
+         Self::Detailed(ref options) => options.command.as_ref(),
+      }
+   }
+
+   /// The command as a single line, the way it is stored in the session file,
+   /// unless the entry leaves it to the app id
+   pub fn command_line(&self) -> Option<String> {
+      match *self.command()? {
+         LaunchCommand::Line(ref line) => Some(line.clone()),
+         LaunchCommand::Argv(ref argv) => Some(shell_words::join(argv)),
+      }
+   }
+
+   /// Environment variables to set for the app
+   pub const fn env(&self) -> Option<&HashMap<String, String>> {
+      match *self {
+         Self::Command(_) => None,
+         Self::Detailed(ref options) => Some(&options.env),
+      }
+   }
+
+   /// Directory to start the app in, with `~` expanded
+   pub fn cwd(&self) -> Option<PathBuf> {
+      let Self::Detailed(ref options) = *self else {
+         return None;
+      };
+      let cwd = options.cwd.as_deref()?;
+      Some(expand_home(cwd))
+   }
+
+   /// App ids whose windows must have appeared before the app is launched
+   pub fn after(&self) -> &[Pattern] {
+      match *self {
+         Self::Command(_) => &[],
+         Self::Detailed(ref options) => &options.after,
+      }
+   }
+
+   /// How long to wait for the app's window to appear
+   pub fn timeout(&self) -> Option<Duration> {
+      let Self::Detailed(ref options) = *self else {
+         return None;
+      };
+      Duration::try_from_secs_f64(options.timeout?).ok()
+   }
+
+   /// Condition that must hold before the app is launched
+   pub const fn wait_for(&self) -> Option<&WaitFor> {
+      match *self {
+         Self::Command(_) => None,
+         Self::Detailed(ref options) => options.wait_for.as_ref(),
+      }
+   }
+}
+
+impl WaitFor {
+   /// How long to wait at most before launching the app anyway
+   pub fn timeout(&self) -> Duration {
+      self
+         .timeout
+         .and_then(|timeout| Duration::try_from_secs_f64(timeout).ok())
+         .unwrap_or(DEFAULT_WAIT_TIMEOUT)
+   }
+
+   /// Whether the condition holds right now
+   pub fn holds(&self) -> bool {
+      self
+         .path
+         .as_deref()
+         .is_none_or(|path| expand_home(path).exists())
+         && self.process.as_deref().is_none_or(process::is_running)
+         && self.command.as_deref().is_none_or(command_succeeds)
+   }
+}
+
+/// Whether a shell command exits successfully within `CHECK_TIMEOUT`. A command
+/// that takes longer, e.g. one waiting for a password, is killed.
+fn command_succeeds(command: &str) -> bool {
+   let mut child = match Command::new("sh")
+      .args(["-c", command])
+      .stdin(Stdio::null())
+      .stdout(Stdio::null())
+      .stderr(Stdio::null())
+      .spawn()
+   {
+      Ok(child) => child,
+      Err(err) => {
+         warn!("failed to run `{command}`: {err}");
+         return false;
+      },
+   };
+
+   let deadline = Instant::now() + CHECK_TIMEOUT;
+   loop {
+      match child.try_wait() {
+         Ok(Some(status)) => return status.success(),
+         Ok(None) if Instant::now() < deadline => thread::sleep(CHECK_POLL_INTERVAL),
+         Ok(None) => {
+            warn!(
+               "`{command}` took longer than {}s, killing it",
+               CHECK_TIMEOUT.as_secs_f64()
+            );
+            let _ = child.kill();
+            let _ = child.wait();
+            return false;
+         },
+         Err(err) => {
+            warn!("failed to wait for `{command}`: {err}");
+            return false;
+         },
+      }
+   }
+}
+
+/// Split a command line into the program and its arguments, following POSIX
+/// shell quoting rules
+pub fn split_command_line(line: &str) -> Vec<String> {
+   shell_words::split(line).unwrap_or_else(|err| {
+      // Better to try launching something than nothing at all
+      warn!("failed to parse command `{line}` ({err}), splitting it on whitespace instead");
+      line.split_whitespace().map(String::from).collect()
+   })
+}
+
+/// Expand a leading `~` to the home directory
+pub fn expand_home(path: &Path) -> PathBuf {
+   match (path.strip_prefix("~"), dirs::home_dir()) {
+      (Ok(rest), Some(home)) => home.join(rest),
+      _ => path.to_path_buf(),
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   fn parse(entries: &str) -> HashMap<String, LaunchEntry> {
+      toml::from_str(entries).unwrap()
+   }
+
+   #[test]
+   fn command_defaults_to_app_id() {
+      let entries = parse(r#""jetbrains-idea" = { timeout = 60 }"#);
+      let entry = &entries["jetbrains-idea"];
+      assert_eq!(entry.command_line(), None);
+      assert_eq!(entry.timeout(), Some(Duration::from_mins(1)));
+   }
+
+   #[test]
+   fn parses_every_command_form() {
+      let entries = parse(
+         r#"
+         line = "flatpak run --command='my app' org.foo.App"
+         argv = ["my-editor", "arg with space"]
+         detailed = { command = "kitty", cwd = "/tmp" }
+         "#,
+      );
+      assert_eq!(
+         entries["line"].command_line().as_deref(),
+         Some("flatpak run --command='my app' org.foo.App")
+      );
+      assert_eq!(
+         entries["argv"].command_line().as_deref(),
+         Some("my-editor 'arg with space'")
+      );
+      assert_eq!(entries["detailed"].command_line().as_deref(), Some("kitty"));
+      assert_eq!(entries["detailed"].cwd(), Some(PathBuf::from("/tmp")));
+   }
+
+   #[test]
+   fn kills_hanging_checks() {
+      let started = Instant::now();
+      assert!(!command_succeeds("sleep 30"));
+      assert!(started.elapsed() < CHECK_TIMEOUT * 2);
+      assert!(command_succeeds("true"));
+      assert!(!command_succeeds("exit 1"));
+   }
+
+   #[test]
+   fn rejects_unknown_options() {
+      assert!(toml::from_str::<HashMap<String, LaunchEntry>>("app = { timout = 60 }").is_err());
+   }
+}
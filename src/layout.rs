@@ -0,0 +1,169 @@
+use std::collections::HashMap;
+
+use niri_ipc::{
+   ColumnDisplay,
+   Output,
+   Window,
+   Workspace,
+};
+use serde::Deserialize;
+
+/// Columns at least this wide (as a proportion of the working area) are
+/// considered full-width, leaving some slack for rounding
+const FULL_WIDTH_THRESHOLD: f64 = 0.97;
+
+/// Gaps between windows in Niri's default config, in logical pixels
+const DEFAULT_GAPS: f64 = 16.0;
+
+/// The `[layout]` section of the config, mirroring the parts of Niri's own
+/// `layout` config that column widths depend on
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LayoutConfig {
+   /// Gaps between windows, as set with `gaps` in Niri's config
+   #[serde(default)]
+   gaps:   Option<f64>,
+   /// Space left free at the sides of the outputs, as set with `struts` in
+   /// Niri's config
+   #[serde(default)]
+   struts: Struts,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Struts {
+   #[serde(default)]
+   left:  f64,
+   #[serde(default)]
+   right: f64,
+}
+
+impl LayoutConfig {
+   /// The proportion Niri needs in `SetColumnWidth` to give a column this
+   /// tile width.
+   ///
+   /// Niri resolves a proportion `p` to `(working_width - gaps) * p - gaps`,
+   /// where the working width is the output width without struts, so this is
+   /// inverted here for the width to survive a save and restore.
+   fn proportion(&self, tile_width: f64, output_width: f64) -> Option<f64> {
+      let gaps = self.gaps.unwrap_or(DEFAULT_GAPS);
+      let available = output_width - self.struts.left - self.struts.right - gaps;
+      (available > 0.0).then(|| (tile_width + gaps) / available)
+   }
+}
+
+/// Layout of a column in the scrolling layout, independent of the output's
+/// resolution and scale
+#[derive(Clone, Copy)]
+pub struct ColumnLayout {
+   /// Column width as the proportion Niri resolves to the column's width, see
+   /// [`LayoutConfig::proportion`]
+   pub width:         Option<f64>,
+   /// Whether the column shows its windows stacked or as tabs
+   pub display:       ColumnDisplay,
+   /// Whether the column spans the whole output
+   pub is_full_width: bool,
+}
+
+/// Work out the layout of every column, keyed by workspace id and column index.
+///
+/// Niri doesn't report column display modes over IPC, so tabbed columns are
+/// recognized by their tiles: in a tabbed column every tile takes up the full
+/// column height, so together they are taller than the output.
+pub fn column_layouts(
+   windows: &[Window],
+   workspaces: &[Workspace],
+   outputs: &HashMap<String, Output>,
+   config: &LayoutConfig,
+) -> HashMap<(u64, usize), ColumnLayout> {
+   let mut columns = HashMap::<(u64, usize), Vec<&Window>>::new();
+   for window in windows.iter().filter(|window| !window.is_floating) {
+      if let Some(workspace_id) = window.workspace_id
+         && let Some((column, _)) = window.layout.pos_in_scrolling_layout
+      {
+         columns
+            .entry((workspace_id, column))
+            .or_default()
+            .push(window);
+      }
+   }
+
+   columns
+      .into_iter()
+      .map(|((workspace_id, column), tiles)| {
+         let output_size = workspaces
+            .iter()
+            .find(|workspace| workspace.id == workspace_id)
+            .and_then(|workspace| workspace.output.as_ref())
+            .and_then(|output| outputs.get(output))
+            .and_then(|output| output.logical)
+            .map(|logical| (f64::from(logical.width), f64::from(logical.height)));
+
+         let tile_width = tiles
+            .iter()
+            .map(|window| window.layout.tile_size.0)
+            .fold(0.0, f64::max);
+         let width =
+            output_size.and_then(|(output_width, _)| config.proportion(tile_width, output_width));
+
+         let total_height = tiles
+            .iter()
+            .map(|window| window.layout.tile_size.1)
+            .sum::<f64>();
+         let display = match output_size {
+            Some((_, output_height)) if tiles.len() > 1 && total_height > output_height => {
+               ColumnDisplay::Tabbed
+            },
+            _ => ColumnDisplay::Normal,
+         };
+
+         let layout = ColumnLayout {
+            width,
+            display,
+            is_full_width: width.is_some_and(|width| width >= FULL_WIDTH_THRESHOLD),
+         };
+         ((workspace_id, column), layout)
+      })
+      .collect()
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   /// The width Niri gives a column with this proportion
+   fn resolve(config: &LayoutConfig, proportion: f64, output_width: f64) -> f64 {
+      let gaps = config.gaps.unwrap_or(DEFAULT_GAPS);
+      (output_width - config.struts.left - config.struts.right - gaps).mul_add(proportion, -gaps)
+   }
+
+   #[test]
+   fn width_survives_save_and_restore() {
+      let configs = [LayoutConfig::default(), LayoutConfig {
+         gaps:   Some(8.0),
+         struts: Struts {
+            left:  64.0,
+            right: 32.0,
+         },
+      }];
+      for config in &configs {
+         for proportion in [1.0 / 3.0, 0.5, 2.0 / 3.0, 1.0] {
+            let mut width = resolve(config, proportion, 1920.0);
+            // Saving and restoring many times over keeps the column as wide
+            for _ in 0..10 {
+               let saved = config.proportion(width, 1920.0).unwrap();
+               assert!(
+                  (saved - proportion).abs() < 1.0e-9,
+                  "{saved} != {proportion}"
+               );
+               width = resolve(config, saved, 1920.0);
+            }
+         }
+      }
+   }
+
+   #[test]
+   fn no_width_without_working_area() {
+      assert!(LayoutConfig::default().proportion(100.0, 0.0).is_none());
+   }
+}
@@ -12,12 +12,10 @@ use std::{
          AtomicBool,
          Ordering,
       },
+      OnceLock,
+      mpsc::RecvTimeoutError,
    },
-   thread,
-   time::{
-      Duration,
-      Instant,
-   },
+   time::Duration,
 };
 
 use anstyle::{
@@ -27,6 +25,7 @@ use anstyle::{
 };
 use clap::{
    Parser,
+   Subcommand,
    builder,
    crate_authors,
 };
@@ -42,31 +41,62 @@ use log::{
    warn,
 };
 use niri_ipc::{
-   Action,
-   Reply,
+   Output,
    Request,
    Response,
-   SizeChange,
    Window,
    Workspace,
-   WorkspaceReferenceArg,
    socket::Socket,
 };
-use serde::{
-   Deserialize,
-   Serialize,
-};
+use regex::Regex;
+use serde::Deserialize;
 use signal_hook::{
    consts::{SIGUSR1, TERM_SIGNALS},
    flag,
 };
 use thiserror::Error;
 
+use crate::{
+   events::SessionState,
+   handlers::{
+      LaunchRule,
+      TmuxConfig,
+   },
+   launch::LaunchEntry,
+   layout::LayoutConfig,
+   multiplexer::Multiplexer,
+   pattern::Pattern,
+   process::ProcessTree,
+   restore::{
+      LateWindows,
+      PlanFormat,
+   },
+   session::{
+      SessionFile,
+      SessionMetadata,
+      SessionWindow,
+   },
+   ssh::SshConfig,
+};
+
+mod events;
+mod handlers;
+mod launch;
+mod layout;
 mod logger;
+mod multiplexer;
+mod pattern;
+mod process;
+mod restore;
+mod session;
+mod shell;
+mod ssh;
+mod storage;
 
 const APP_NAME: &str = env!("CARGO_PKG_NAME");
 
-const WINDOW_POLL_INTERVAL: Duration = Duration::from_millis(250);
+/// Number of previous session files kept by default
+const DEFAULT_BACKUPS: usize = 5;
 
 #[derive(Debug, Error)]
 pub enum NiriError {
@@ -80,53 +110,96 @@ pub enum NiriError {
 
 type NiriResult<T> = Result<T, NiriError>;
 
-/// Window data for session persistence
-#[derive(Serialize, Deserialize)]
-struct SessionWindow<'niri> {
-   id:               u64,
-   /// The application id of the window, see <https://wayland-book.com/xdg-shell-basics/xdg-toplevel.html>
-   app_id:           Option<String>,
-   /// The window title (used for extracting project paths for IDEs like PyCharm)
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Config {
+   #[serde(default)]
+   skip:    Skip,
+   /// Map `app_id` to actual launch command (e.g.,
+   /// "thorium-discord.com__app-Default" -> "discord-web-app")
+   #[serde(default)]
+   launch:  HashMap<String, LaunchEntry>,
+   /// Rules that relaunch windows based on their app id and title, tried
+   /// before the built-in app handlers
+   #[serde(default, rename = "rule")]
+   rules:   Vec<LaunchRule>,
+   /// How kitty windows showing tmux are recognized and reattached
+   #[serde(default)]
+   tmux:    TmuxConfig,
+   /// How hosts in window titles are connected to over SSH
    #[serde(default)]
-   title:            Option<String>,
-   /// The launch command to spawn this window (mapped from `app_id` via config,
-   /// otherwise `app_id` if no mapping exists)
-   launch_command:   Option<String>,
-   /// Index of the workspace on the corresponding monitor
-   workspace_idx:    Option<u8>,
-   /// Name of the workspace, in case of a named workspace
-   workspace_name:   Option<&'niri str>,
-   /// Output the workspace is on
-   workspace_output: Option<&'niri str>,
-   /// Whether the window is focused or not
-   is_focused:       bool,
-   /// Window size (width, height) in logical pixels
-   /// TODO: Remove [`Option`] in a month
+   ssh:     SshConfig,
+   /// Number of previous session files to keep as `session.json.1..N`
    #[serde(default)]
-   window_size:      Option<(i32, i32)>,
-   /// Position in the scrolling layout: (column index, tile index within column)
-   /// Column index starts from 1 (leftmost), tile index starts from 1 (topmost)
-   /// Used to restore the exact layout including vertical stacking within columns
+   backups: Option<usize>,
+   /// Niri's layout settings that saved column widths depend on
    #[serde(default)]
-   layout_position:  Option<(usize, usize)>,
+   layout:  LayoutConfig,
+   #[serde(default)]
+   restore: RestoreConfig,
 }
 
-#[derive(Default, Deserialize)]
+#[derive(Deserialize, Default)]
 #[serde(deny_unknown_fields)]
-struct Config {
+struct RestoreConfig {
+   /// How many windows may be spawned ahead of the one being placed
    #[serde(default)]
-   skip:   Skip,
-   /// Map `app_id` to actual launch command (e.g.,
-   /// "thorium-discord.com__app-Default" -> "discord-web-app")
+   parallel:      Option<usize>,
+   /// App ids, as globs or `/regex/`, that are relaunched with the command
+   /// line recorded at save time rather than their launch command
+   #[serde(default)]
+   exact:         Vec<Pattern>,
+   /// How long to wait for a spawned window to appear, in seconds
+   #[serde(default)]
+   timeout:       Option<f64>,
+   /// How often unmatched windows are reconsidered, in seconds
    #[serde(default)]
-   launch: HashMap<String, String>,
+   poll_interval: Option<f64>,
+   /// How long the daemon keeps watching for windows that didn't appear
+   /// during the restore, in seconds
+   #[serde(default)]
+   grace_period:  Option<f64>,
 }
 
 #[derive(Deserialize, Default)]
 #[serde(deny_unknown_fields)]
 struct Skip {
+   /// Launch commands whose windows are skipped
+   #[serde(default)]
+   apps:       Vec<String>,
+   /// App ids whose windows are skipped, as globs or `/regex/`
+   #[serde(default)]
+   app_ids:    Vec<Pattern>,
+   /// Regexes matching the titles of windows to skip
+   #[serde(default, deserialize_with = "pattern::deserialize_regexes")]
+   titles:     Vec<Regex>,
+   /// Names of workspaces whose windows are skipped, as globs or `/regex/`
+   #[serde(default)]
+   workspaces: Vec<Pattern>,
+   /// Outputs whose windows are skipped, as globs or `/regex/`
    #[serde(default)]
-   apps: Vec<String>,
+   outputs:    Vec<Pattern>,
+}
+
+impl Skip {
+   /// Whether the window should be left out of saved and restored sessions
+   fn is_skipped(&self, window: &SessionWindow) -> bool {
+      let matches_any = |patterns: &[Pattern], value: Option<&str>| {
+         value.is_some_and(|value| patterns.iter().any(|pattern| pattern.is_match(value)))
+      };
+
+      window
+         .launch_command
+         .as_ref()
+         .is_some_and(|launch_command| self.apps.contains(launch_command))
+         || matches_any(&self.app_ids, window.app_id.as_deref())
+         || window
+            .title
+            .as_deref()
+            .is_some_and(|title| self.titles.iter().any(|regex| regex.is_match(title)))
+         || matches_any(&self.workspaces, window.workspace_name.as_deref())
+         || matches_any(&self.outputs, window.workspace_output.as_deref())
+   }
 }
 
 #[derive(Parser)]
@@ -148,15 +221,57 @@ struct Skip {
     )
 )]
 struct Args {
-   /// Save interval in seconds
-   #[arg(long, default_value = "300")]
-   save_interval: u64,
+   #[command(subcommand)]
+   command: Option<Command>,
+
+   /// Seconds to wait for layout changes to settle before saving the session
+   #[arg(long, default_value = "2")]
+   save_debounce: u64,
+
+   /// Deprecated: sessions are now saved as soon as the layout settles
+   #[arg(long, hide = true)]
+   save_interval: Option<u64>,
 
    /// Enable debug output
-   #[arg(long, short)]
+   #[arg(long, short, global = true)]
    debug: bool,
 }
 
+/// Without a subcommand, nirinit runs as a daemon that restores the last
+/// session and keeps it up to date.
+#[derive(Subcommand)]
+enum Command {
+   /// Save the current layout as a named session
+   Save {
+      /// Name of the session
+      name:  String,
+      /// Save even if the current layout has no windows
+      #[arg(long)]
+      force: bool,
+   },
+   /// Restore a named session, or the last session if no name is given
+   Restore {
+      /// Name of the session
+      name:        Option<String>,
+      /// Restore the N-th previous generation of the session instead
+      #[arg(long, value_name = "N", value_parser = clap::value_parser!(u16).range(1..))]
+      from_backup: Option<u16>,
+      /// Print what would be restored instead of spawning anything
+      #[arg(long)]
+      dry_run:     bool,
+      /// Output format of the dry run
+      #[arg(long, value_enum, default_value_t, requires = "dry_run")]
+      format:      PlanFormat,
+   },
+   /// List the saved named sessions
+   List,
+   /// Delete a named session
+   Delete {
+      /// Name of the session
+      name: String,
+   },
+}
+
 fn load_config() -> eyre::Result<Config> {
    let config_path = config_file()?;
 
@@ -198,13 +313,87 @@ fn niri_workspaces() -> NiriResult<Vec<Workspace>> {
    }
 }
 
-fn data_file() -> eyre::Result<PathBuf> {
+/// Niri's version, queried once and cached for the lifetime of the process
+fn niri_version() -> Option<String> {
+   static VERSION: OnceLock<Option<String>> = OnceLock::new();
+
+   VERSION
+      .get_or_init(|| {
+         let mut socket = Socket::connect().ok()?;
+         match socket.send(Request::Version).ok()? {
+            Ok(Response::Version(version)) => Some(version),
+            _ => None,
+         }
+      })
+      .clone()
+}
+
+fn niri_outputs() -> NiriResult<HashMap<String, Output>> {
+   let mut socket = Socket::connect().map_err(NiriError::Connect)?;
+   match socket
+      .send(Request::Outputs)
+      .map_err(NiriError::Send)?
+      .map_err(NiriError::Reply)?
+   {
+      Response::Outputs(outputs) => Ok(outputs),
+      other => {
+         Err(NiriError::Reply(format!(
+            "Unexpected response from Niri: {other:?}"
+         )))
+      },
+   }
+}
+
+fn data_dir() -> eyre::Result<PathBuf> {
    let data_dir = dirs::data_dir()
       .ok_or_eyre("Failed to locate the data directory ($XDG_DATA_HOME)")?
       .join(APP_NAME);
    fs::create_dir_all(&data_dir)
       .wrap_err_with(|| format!("Failed to create data directory: {}", data_dir.display()))?;
-   Ok(data_dir.join("session.json"))
+   Ok(data_dir)
+}
+
+fn data_file() -> eyre::Result<PathBuf> {
+   Ok(data_dir()?.join("session.json"))
+}
+
+fn sessions_dir() -> eyre::Result<PathBuf> {
+   let sessions_dir = data_dir()?.join("sessions");
+   fs::create_dir_all(&sessions_dir).wrap_err_with(|| {
+      format!(
+         "Failed to create sessions directory: {}",
+         sessions_dir.display()
+      )
+   })?;
+   Ok(sessions_dir)
+}
+
+/// Path of the snapshot file for a named session
+fn named_session_file(name: &str) -> eyre::Result<PathBuf> {
+   if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\']) {
+      eyre::bail!("Invalid session name: {name:?}");
+   }
+   Ok(sessions_dir()?.join(format!("{name}.json")))
+}
+
+/// Names of all saved named sessions, sorted alphabetically
+fn named_sessions() -> eyre::Result<Vec<String>> {
+   let sessions_dir = sessions_dir()?;
+   let entries = fs::read_dir(&sessions_dir).wrap_err_with(|| {
+      format!(
+         "Failed to read sessions directory: {}",
+         sessions_dir.display()
+      )
+   })?;
+
+   let mut names = entries
+      .filter_map(Result::ok)
+      .map(|entry| entry.path())
+      .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+      .filter_map(|path| Some(path.file_stem()?.to_str()?.to_owned()))
+      .collect::<Vec<_>>();
+   names.sort();
+   Ok(names)
 }
 
 fn config_file() -> eyre::Result<PathBuf> {
@@ -229,23 +418,80 @@ fn find_workspace_for_window<'niri>(
       .find(|w| window.workspace_id == Some(w.id))
 }
 
-/// Save the session
-fn save_session(file_path: &Path, config: &Config) -> eyre::Result<()> {
+/// Save the session, querying the current layout from Niri
+fn save_session(file_path: &Path, config: &Config, force: bool) -> eyre::Result<()> {
    let windows = niri_windows()?;
    let workspaces = niri_workspaces()?;
+   let outputs = niri_outputs()?;
+
+   write_session(file_path, config, windows, &workspaces, &outputs, force)
+}
+
+/// The session currently stored at `file_path`, if it exists and is readable
+fn read_session(file_path: &Path) -> Option<SessionFile> {
+   let data = fs::read_to_string(file_path).ok()?;
+   session::parse(&data).ok()
+}
+
+/// Write the given windows and workspaces to the session file.
+///
+/// Unless `force` is set, an empty layout never replaces a session that still
+/// has windows in it, e.g. when Niri is already tearing down on shutdown.
+fn write_session(
+   file_path: &Path,
+   config: &Config,
+   windows: Vec<Window>,
+   workspaces: &[Workspace],
+   outputs: &HashMap<String, Output>,
+   force: bool,
+) -> eyre::Result<()> {
+   let current = read_session(file_path);
+
+   if windows.is_empty()
+      && !force
+      && current
+         .as_ref()
+         .is_some_and(|session| !session.windows.is_empty())
+   {
+      warn!(
+         "not overwriting {} with an empty session",
+         file_path.display()
+      );
+      return Ok(());
+   }
+
+   let columns = layout::column_layouts(&windows, workspaces, outputs, &config.layout);
+   let processes = ProcessTree::snapshot();
+   let mut windows_per_pid = HashMap::<i32, usize>::new();
+   for pid in windows.iter().filter_map(|window| window.pid) {
+      *windows_per_pid.entry(pid).or_default() += 1;
+   }
 
    let session_windows = windows
       .into_iter()
       .map(|window| {
-         let workspace = find_workspace_for_window(&window, &workspaces);
+         let workspace = find_workspace_for_window(&window, workspaces);
+         let column = window
+            .workspace_id
+            .zip(window.layout.pos_in_scrolling_layout)
+            .and_then(|(workspace_id, (col, _))| columns.get(&(workspace_id, col)));
+
+         // Windows sharing a process, like those of a foot server or kitty's
+         // `new_os_window`, can't be told apart by what runs in that process
+         // or where it runs
+         let own_pid = window
+            .pid
+            .filter(|pid| windows_per_pid.get(pid) == Some(&1));
+         // The program running in a terminal window, if it is one
+         let foreground = own_pid.and_then(|pid| processes.foreground_process(pid));
 
          // Map app_id to launch command if it exists in the config
-         let launch_command = window.app_id.as_ref().and_then(|app_id| {
+         let launch_command = window.app_id.as_ref().map(|app_id| {
             config
                .launch
                .get(app_id)
-               .cloned()
-               .or_else(|| Some(app_id.clone()))
+               .and_then(LaunchEntry::command_line)
+               .unwrap_or_else(|| app_id.clone())
          });
 
          SessionWindow {
@@ -254,472 +500,135 @@ fn save_session(file_path: &Path, config: &Config) -> eyre::Result<()> {
             title: window.title,
             launch_command,
             workspace_idx: workspace.map(|w| w.idx),
-            workspace_name: workspace.and_then(|w| w.name.as_deref()),
-            workspace_output: workspace.and_then(|w| w.output.as_deref()),
+            workspace_name: workspace.and_then(|w| w.name.clone()),
+            workspace_output: workspace.and_then(|w| w.output.clone()),
             is_focused: window.is_focused,
             window_size: Some(window.layout.window_size),
             layout_position: window.layout.pos_in_scrolling_layout,
+            is_floating: window.is_floating,
+            floating_position: window
+               .is_floating
+               .then_some(window.layout.tile_pos_in_workspace_view)
+               .flatten(),
+            column_width: column.and_then(|column| column.width),
+            column_display: column.map(|column| column.display),
+            is_full_width: column.is_some_and(|column| column.is_full_width),
+            cwd: own_pid
+               .and_then(|pid| process::cwd(foreground.unwrap_or(pid)))
+               .and_then(|cwd| cwd.into_os_string().into_string().ok()),
+            cmdline: window.pid.and_then(process::cmdline),
+            multiplexer: foreground.and_then(Multiplexer::detect),
          }
       })
+      .filter(|window| {
+         let skipped = config.skip.is_skipped(window);
+         if skipped {
+            debug!(
+               "not saving window {} (app_id: {:?})",
+               window.id, window.app_id
+            );
+         }
+         !skipped
+      })
       .collect::<Vec<_>>();
 
-   let json_data = serde_json::to_string_pretty(&session_windows)
+   if current.is_some_and(|session| session.windows == session_windows) {
+      debug!("session at {} is unchanged", file_path.display());
+      return Ok(());
+   }
+
+   let metadata = SessionMetadata::now(get_local_hostname(), niri_version());
+   let json_data = serde_json::to_string_pretty(&SessionFile::new(session_windows, metadata))
       .wrap_err("Failed to serialize session data")?;
 
-   fs::write(file_path, json_data)
+   let backups = config.backups.unwrap_or(DEFAULT_BACKUPS);
+   storage::write_atomic(file_path, json_data.as_bytes(), backups)
       .wrap_err_with(|| format!("Failed to write to session file: {}", file_path.display()))?;
    debug!("saved session to {}", file_path.display());
    Ok(())
 }
 
-/// Extract project path from JetBrains IDE window title.
-///
-/// JetBrains IDEs (PyCharm, IntelliJ, etc.) display the project path in the window title
-/// in the format: "project_name [/path/to/project] – current_file.ext"
-///
-/// This function extracts the path between square brackets and expands `~` to the home directory.
-///
-/// # Arguments
-/// * `title` - The window title from niri IPC
-///
-/// # Returns
-/// * `Some(path)` - The expanded absolute path to the project
-/// * `None` - If the title doesn't contain a valid path in brackets
-///
-/// # Example
-/// ```
-/// // Input:  "camel [~/projects/camel] – main.py"
-/// // Output: Some("/home/user/projects/camel")
-/// ```
-fn extract_jetbrains_project_path(title: &str) -> Option<String> {
-   // Find the path between square brackets: [path]
-   let start = title.find('[')?;
-   let end = title.find(']')?;
-
-   // Sanity check: start must be before end
-   if start >= end {
-      return None;
-   }
-
-   // Extract the path (excluding the brackets themselves)
-   let path = &title[start + 1..end];
-
-   // Expand ~ to the user's home directory
-   // e.g., "~/projects" -> "/home/username/projects"
-   if path.starts_with('~') {
-      dirs::home_dir()
-         .map(|home| path.replacen('~', home.to_str().unwrap_or(""), 1))
-   } else {
-      Some(path.to_string())
-   }
-}
-
-/// Information extracted from a tmux window title
-struct TmuxInfo {
-   /// The hostname where tmux is running (could be local or remote)
-   hostname: String,
-   /// The tmux session name
-   session: String,
-}
-
-/// Extract hostname and tmux session name from kitty window title.
-///
-/// When running tmux inside kitty, the window title follows the format set by oh-my-tmux:
-/// `hostname ❐ session_name ● window_index program_name`
-///
-/// For example:
-/// - Local:  "YuzhouArch ❐ main ● 1 claude"
-/// - Remote: "surfi1 ❐ dt-agent ● 2 zsh"
-///
-/// The `❐` (U+2750) marks the boundary between hostname and session name.
-/// The `●` (U+25CF) marks the end of session name.
-///
-/// # Arguments
-/// * `title` - The window title from niri IPC
-///
-/// # Returns
-/// * `Some(TmuxInfo)` - The hostname and tmux session name
-/// * `None` - If the title doesn't match the expected format
-///
-/// # Example
-/// ```
-/// // Input:  "surfi1 ❐ dt-agent ● 2 zsh"
-/// // Output: Some(TmuxInfo { hostname: "surfi1", session: "dt-agent" })
-/// ```
-fn extract_tmux_info(title: &str) -> Option<TmuxInfo> {
-   // Find the markers: hostname ❐ session ● ...
-   let start_marker = '❐';
-   let end_marker = '●';
-
-   let start = title.find(start_marker)?;
-   let end = title.find(end_marker)?;
-
-   if start >= end {
-      return None;
-   }
-
-   // Extract hostname (everything before ❐)
-   let hostname = title[..start].trim();
-   if hostname.is_empty() {
-      return None;
-   }
-
-   // Extract session name (between ❐ and ●)
-   let session = title[start + start_marker.len_utf8()..end].trim();
-   if session.is_empty() {
-      return None;
-   }
-
-   Some(TmuxInfo {
-      hostname: hostname.to_string(),
-      session: session.to_string(),
-   })
-}
-
-/// Get the local machine's hostname
+/// Get the local machine's hostname, as the kernel knows it
 fn get_local_hostname() -> Option<String> {
-   std::fs::read_to_string("/etc/hostname")
+   gethostname::gethostname()
+      .into_string()
       .ok()
-      .map(|s| s.trim().to_string())
+      .filter(|hostname| !hostname.is_empty())
 }
 
-/// Get Microsoft Edge workspace ID from workspace name.
-///
-/// Edge stores workspace information in a JSON cache file at:
-/// `~/.config/microsoft-edge/Default/Workspaces/WorkspacesCache`
-///
-/// The window title of an Edge workspace window is the workspace name itself,
-/// so we can use it to look up the corresponding workspace ID.
-///
-/// Edge can then be launched with `--launch-workspace=<ID>` to open that workspace.
-///
-/// # Arguments
-/// * `workspace_name` - The workspace name (from window title)
-///
-/// # Returns
-/// * `Some(id)` - The UUID of the workspace
-/// * `None` - If the cache file doesn't exist or workspace not found
-///
-/// # Example
-/// ```
-/// // Workspace name: "vllm"
-/// // Returns: Some("bbb831b3-9481-4fa9-872e-2b7344417362")
-/// ```
-fn get_edge_workspace_id(workspace_name: &str) -> Option<String> {
-   // Build path to Edge's workspace cache file
-   // Location: ~/.config/microsoft-edge/Default/Workspaces/WorkspacesCache
-   let cache_path = dirs::config_dir()?
-      .join("microsoft-edge/Default/Workspaces/WorkspacesCache");
-
-   // Read and parse the JSON cache file
-   let cache_content = fs::read_to_string(&cache_path).ok()?;
-   let cache: serde_json::Value = serde_json::from_str(&cache_content).ok()?;
-
-   // Search for a workspace with matching name and return its ID
-   // Cache structure: { "workspaces": [{ "name": "...", "id": "..." }, ...] }
-   cache["workspaces"]
-      .as_array()?
-      .iter()
-      .find(|ws| ws["name"].as_str() == Some(workspace_name))
-      .and_then(|ws| ws["id"].as_str())
-      .map(|id| id.to_string())
-}
-
-fn spawn_and_move_window<'niri>(
-   launch_command: &str,
-   app_id: &str,
-   title: Option<&str>,
-   workspace_idx: Option<u8>,
-   workspace_name: Option<&'niri str>,
-   workspace_output: Option<&'niri str>,
-   window_size: Option<(i32, i32)>,
-   layout_position: Option<(usize, usize)>,
-) -> eyre::Result<()> {
-   info!(
-      "restoring window: app_id={}, workspace_name={:?}, workspace_idx={:?}, output={:?}, layout_pos={:?}",
-      app_id, workspace_name, workspace_idx, workspace_output, layout_position
-   );
-   // Build the launch command with app-specific arguments.
-   //
-   // Different applications need different handling to restore their state:
-   // - JetBrains IDEs: Pass project path as argument (e.g., `pycharm /path/to/project`)
-   // - Microsoft Edge: Pass workspace ID (e.g., `edge --launch-workspace=<uuid>`)
-   // - Other apps: Just launch without special arguments
-   let command = if app_id.starts_with("jetbrains-") {
-      // JetBrains IDEs (PyCharm, IntelliJ, WebStorm, etc.)
-      // Window title format: "project_name [/path/to/project] – filename"
-      // We extract the path and pass it as an argument to open the correct project
-      if let Some(title) = title {
-         if let Some(project_path) = extract_jetbrains_project_path(title) {
-            debug!("extracted project path for {app_id}: {project_path}");
-            // Launch with project path: `pycharm /home/user/projects/myproject`
-            vec![launch_command.to_owned(), project_path]
-         } else {
-            // Fallback: launch without project path (will open last project or welcome screen)
-            vec![launch_command.to_owned()]
-         }
-      } else {
-         vec![launch_command.to_owned()]
-      }
-   } else if app_id == "microsoft-edge" {
-      // Microsoft Edge with Workspaces feature
-      // Window title IS the workspace name (e.g., "vllm", "work", "personal")
-      // We look up the workspace ID from Edge's cache and launch with --launch-workspace
-      // Note: launch_command may contain args (e.g., "microsoft-edge-stable --force-device-scale-factor=1.1")
-      let mut cmd_parts: Vec<String> = launch_command.split_whitespace().map(String::from).collect();
-      if let Some(workspace_name) = title {
-         if let Some(workspace_id) = get_edge_workspace_id(workspace_name) {
-            debug!("found Edge workspace ID for '{workspace_name}': {workspace_id}");
-            // Launch with workspace: `microsoft-edge-stable [args] --launch-workspace=<uuid>`
-            cmd_parts.push(format!("--launch-workspace={}", workspace_id));
-            cmd_parts
-         } else {
-            // Workspace not found in cache (maybe deleted or new profile)
-            debug!("no Edge workspace found for '{workspace_name}'");
-            cmd_parts
-         }
-      } else {
-         cmd_parts
-      }
-   } else if app_id == "kitty" {
-      // Kitty terminal with tmux session (local or remote via SSH)
-      // Window title format: "hostname ❐ session_name ● window_index program_name"
-      //
-      // Examples:
-      // - Local:  "YuzhouArch ❐ main ● 1 zsh"     -> tmux attach -t main
-      // - Remote: "surfi1 ❐ dt-agent ● 2 zsh"    -> ssh surfi1 -t tmux attach -t dt-agent
-      if let Some(title) = title {
-         if let Some(tmux_info) = extract_tmux_info(title) {
-            let local_hostname = get_local_hostname();
-            let is_local = local_hostname
-               .as_ref()
-               .map(|h| h.eq_ignore_ascii_case(&tmux_info.hostname))
-               .unwrap_or(false);
-
-            if is_local {
-               // Local tmux session
-               // tmux server is started at niri startup, tmux-continuum auto-restores sessions.
-               // Just wait for the session to appear and attach.
-               debug!("found local tmux session: {}", tmux_info.session);
-               vec![
-                  launch_command.to_owned(),
-                  "-e".to_owned(),
-                  "sh".to_owned(),
-                  "-c".to_owned(),
-                  format!(
-                     "for i in $(seq 1 30); do tmux has-session -t {0} 2>/dev/null && break; sleep 0.5; done; \
-                      tmux attach -t {0} || tmux new-session \\; choose-tree -s",
-                     tmux_info.session
-                  ),
-               ]
-            } else {
-               // Remote tmux session via SSH
-               // Retry SSH connection if DNS/network not ready yet
-               debug!(
-                  "found remote tmux session: {} on host {}",
-                  tmux_info.session, tmux_info.hostname
-               );
-               vec![
-                  launch_command.to_owned(),
-                  "-e".to_owned(),
-                  "sh".to_owned(),
-                  "-c".to_owned(),
-                  format!(
-                     "for retry in $(seq 1 10); do \
-                        ssh -t {} 'for i in $(seq 1 20); do tmux has-session -t {} 2>/dev/null && break; sleep 0.5; done; \
-                        tmux attach -t {} || tmux new-session \\; choose-tree -s' && exit 0; \
-                        echo \"SSH failed, retrying in 2s... (attempt $retry/10)\"; \
-                        sleep 2; \
-                      done; \
-                      echo \"SSH failed after 10 attempts\"; read -p \"Press Enter to close...\"",
-                     tmux_info.hostname, tmux_info.session, tmux_info.session
-                  ),
-               ]
-            }
-         } else {
-            // No tmux session detected, just launch kitty normally
-            vec![launch_command.to_owned()]
-         }
-      } else {
-         vec![launch_command.to_owned()]
-      }
-   } else {
-      // All other applications: just launch with the configured command
-      // Split by whitespace in case launch_command contains args
-      launch_command.split_whitespace().map(String::from).collect()
-   };
-
-   let mut socket = Socket::connect().wrap_err("Failed to connect to Niri IPC socket")?;
-
-   // Record existing window IDs before spawning, so we can find the NEW window after spawn
-   let existing_window_ids: std::collections::HashSet<u64> = niri_windows()?
-      .iter()
-      .map(|w| w.id)
-      .collect();
-
-   debug!("spawning command: {:?}", command);
-   let reply = socket
-      .send(Request::Action(Action::Spawn { command }))
-      .map_err(NiriError::Send)?;
-
-   let Reply::Ok(Response::Handled) = reply else {
-      error!("failed to spawn command `{launch_command}`");
-      return Ok(());
-   };
-   debug!("spawn successful, waiting for window to appear...");
-   debug!(
-      "existing window IDs: {:?}, looking for app_id={}",
-      existing_window_ids, app_id
-   );
-
-   // Always use Index for workspace reference (Name doesn't work for unnamed workspaces)
-   let Some(idx) = workspace_idx else {
-      return Ok(());
-   };
-   let workspace_reference = WorkspaceReferenceArg::Index(idx);
-
-   for attempt in 0..40 {
-      thread::sleep(WINDOW_POLL_INTERVAL);
-
-      let windows = niri_windows()?;
-
-      // Find a NEW window with matching app_id (not in existing_window_ids)
-      let Some(new_window) = windows.iter().find(|w| {
-         w.app_id.as_deref() == Some(app_id) && !existing_window_ids.contains(&w.id)
-      }) else {
-         if attempt % 8 == 7 {
-            // Log every 2 seconds
-            let current_ids: Vec<_> = windows.iter().map(|w| (w.id, w.app_id.as_deref())).collect();
-            debug!("attempt {}: still waiting, current windows: {:?}", attempt + 1, current_ids);
-         }
-         continue;
-      };
-
-      if let Some(output) = workspace_output
-         && let Err(err) = socket.send(Request::Action(Action::MoveWindowToMonitor {
-            id:     Some(new_window.id),
-            output: output.to_owned(),
-         }))
-      {
-         warn!(
-            "failed to move window {}: {err}",
-            new_window
-               .app_id
-               .as_ref()
-               .map_or_else(String::new, |app_id| format!("(app_id: {app_id})")),
-         );
-      }
-
-      // Move window to the correct workspace
-      // This will automatically create the workspace if it doesn't exist
-      socket
-         .send(Request::Action(Action::MoveWindowToWorkspace {
-            window_id: Some(new_window.id),
-            reference: workspace_reference,
-            focus:     false,
-         }))
-         .map_err(NiriError::Send)?
-         .map_err(NiriError::Reply)?;
-
-      debug!(
-         "moved window {} to workspace {:?}",
-         new_window.id,
-         workspace_name.or(workspace_idx.map(|i| i.to_string()).as_deref())
-      );
-
-      // Set workspace name AFTER moving window (workspace now exists)
-      if let Some(name) = workspace_name {
-         info!("setting workspace {} name to '{}'", idx, name);
-         if let Err(err) = socket.send(Request::Action(Action::SetWorkspaceName {
-            name:      name.to_owned(),
-            workspace: Some(WorkspaceReferenceArg::Index(idx)),
-         })) {
-            warn!("failed to set workspace name '{}': {}", name, err);
-         }
-      }
-
-      // Handle vertical stacking within columns
-      // If tile > 1, this window should be stacked below another window in the same column.
-      // We consume it into the column to the left (which contains the previous tile).
-      if let Some((col, tile)) = layout_position {
-         if tile > 1 {
-            debug!(
-               "window {} is at tile {} in column {}, consuming into column to the left",
-               new_window.id, tile, col
-            );
-            // ConsumeOrExpelWindowLeft will merge this window into the column to its left
-            if let Err(err) = socket.send(Request::Action(Action::ConsumeOrExpelWindowLeft {
-               id: Some(new_window.id),
-            })) {
-               warn!(
-                  "failed to consume window {} into column: {}",
-                  new_window.id, err
-               );
-            }
-         }
-      }
-
-      if let Some((width, height)) = window_size {
-         if let Err(err) = socket.send(Request::Action(Action::SetWindowWidth {
-            id:     Some(new_window.id),
-            change: SizeChange::SetFixed(width),
-         })) {
-            warn!(
-               "failed to restore window width for {}: {err}",
-               new_window.app_id.as_deref().unwrap_or("unknown")
-            );
-         }
-
-         if let Err(err) = socket.send(Request::Action(Action::SetWindowHeight {
-            id:     Some(new_window.id),
-            change: SizeChange::SetFixed(height),
-         })) {
-            warn!(
-               "failed to restore window height for {}: {err}",
-               new_window.app_id.as_deref().unwrap_or("unknown")
-            );
-         }
-      }
-
-      // Verify window still exists after all operations
-      thread::sleep(Duration::from_millis(500));
-      let windows_after = niri_windows()?;
-      if windows_after.iter().any(|w| w.id == new_window.id) {
-         debug!("window {} still exists after restore", new_window.id);
-      } else {
-         warn!(
-            "window {} ({}) disappeared immediately after restore!",
-            new_window.id,
-            new_window.app_id.as_deref().unwrap_or("unknown")
-         );
-      }
-
+/// Save the session from the in-memory layout model
+fn save_state(file_path: &Path, config: &Config, state: &mut SessionState) -> eyre::Result<()> {
+   if !state.is_ready() {
+      debug!("no layout received from Niri yet, not saving");
       return Ok(());
    }
 
-   warn!("window for `{launch_command}` did not appear within 10s");
+   // Outputs aren't part of the event stream, so they're queried on demand
+   let outputs = niri_outputs().unwrap_or_else(|err| {
+      warn!("failed to query outputs, column widths won't be saved: {err}");
+      HashMap::new()
+   });
 
+   write_session(
+      file_path,
+      config,
+      state.windows(),
+      &state.workspaces(),
+      &outputs,
+      false,
+   )
+   .inspect_err(|_| state.mark_save_failed())?;
+   state.mark_saved();
    Ok(())
 }
 
-fn restore_session(config: &Config, session_path: &Path) -> eyre::Result<()> {
+/// Restore the session in a file, returning the windows that didn't appear
+/// in time
+fn restore_session(config: &Config, session_path: &Path) -> eyre::Result<Option<LateWindows>> {
    if !session_path.exists() {
-      save_session(session_path, config)?;
-      return Ok(());
+      save_session(session_path, config, false)?;
+      return Ok(None);
    }
 
    info!("restoring previous session");
 
+   let windows = load_session_windows(config, session_path)?;
+   if windows.is_empty() {
+      return Ok(None);
+   }
+
+   // NOTE: We do NOT manually trigger tmux-resurrect restore here.
+   // Instead, we rely on tmux-continuum (@continuum-restore 'on') to automatically
+   // restore sessions when the tmux server starts. The first kitty terminal will
+   // start the tmux server, and continuum will handle the restoration.
+
+   // Workspace names are set AFTER moving windows (in restore::place_window)
+   // because SetWorkspaceName doesn't create workspaces - it only names existing ones.
+
+   let late_windows = restore::restore_windows(config, &windows)?;
+
+   info!("restored session");
+   Ok(late_windows)
+}
+
+/// Load the windows of a session file in the order they are restored, leaving
+/// out skipped apps
+fn load_session_windows(config: &Config, session_path: &Path) -> eyre::Result<Vec<SessionWindow>> {
    let session_data = fs::read_to_string(session_path).wrap_err("Failed to read session file")?;
    if session_data.is_empty() {
       info!("session file at {} is empty", session_path.display());
-      return Ok(());
+      return Ok(Vec::new());
    }
 
-   let windows = serde_json::from_str::<Vec<SessionWindow>>(&session_data)
-      .wrap_err("Failed to load session data")?;
+   let session = session::parse(&session_data)?;
+   let windows = session.windows;
 
-   info!("loaded {} windows from session file", windows.len());
+   info!(
+      "loaded {} windows from session file (saved on {} with Niri {})",
+      windows.len(),
+      session.metadata.hostname.as_deref().unwrap_or("unknown host"),
+      session.metadata.niri_version.as_deref().unwrap_or("unknown"),
+   );
 
    // Sort windows by (workspace_output, workspace_idx, column, tile).
    // This ensures:
@@ -728,44 +637,25 @@ fn restore_session(config: &Config, session_path: &Path) -> eyre::Result<()> {
    // 3. Windows are spawned left-to-right (by column)
    // 4. Within each column, windows are spawned top-to-bottom (by tile)
    let mut sorted_windows = windows;
-   sorted_windows.sort_by_key(|w| {
+   sorted_windows.sort_by_cached_key(|w| {
       let (col, tile) = w.layout_position.unwrap_or((usize::MAX, usize::MAX));
-      (w.workspace_output, w.workspace_idx, col, tile)
+      (w.workspace_output.clone(), w.workspace_idx, col, tile)
    });
 
-   // NOTE: We do NOT manually trigger tmux-resurrect restore here.
-   // Instead, we rely on tmux-continuum (@continuum-restore 'on') to automatically
-   // restore sessions when the tmux server starts. The first kitty terminal will
-   // start the tmux server, and continuum will handle the restoration.
-
-   // Workspace names are set AFTER moving windows (in spawn_and_move_window)
-   // because SetWorkspaceName doesn't create workspaces - it only names existing ones.
-
-   for window in sorted_windows {
-      // Check if the launch command should be skipped
-      if let Some(ref launch_command) = window.launch_command {
-         if config.skip.apps.contains(launch_command) {
-            info!("skipping app: {launch_command}");
-            continue;
-         }
-
-         if let Some(ref app_id) = window.app_id {
-            spawn_and_move_window(
-               launch_command,
-               app_id,
-               window.title.as_deref(),
-               window.workspace_idx,
-               window.workspace_name,
-               window.workspace_output,
-               window.window_size,
-               window.layout_position,
-            )?;
-         }
+   // Sessions saved before a skip rule was added may still contain the window
+   sorted_windows.retain(|window| {
+      let skipped = config.skip.is_skipped(window);
+      if skipped {
+         info!(
+            "skipping window: app_id={}, title={:?}",
+            window.app_id.as_deref().unwrap_or("unknown"),
+            window.title.as_deref().unwrap_or_default()
+         );
       }
-   }
+      !skipped
+   });
 
-   info!("restored session");
-   Ok(())
+   Ok(sorted_windows)
 }
 
 #[must_use]
@@ -815,6 +705,62 @@ fn main() -> eyre::Result<()> {
       Config::default()
    });
 
+   match args.command {
+      Some(Command::Save { ref name, force }) => {
+         let session_path = named_session_file(name)?;
+         save_session(&session_path, &config, force)?;
+         info!("saved session '{name}'");
+         Ok(())
+      },
+      Some(Command::Restore {
+         ref name,
+         from_backup,
+         dry_run,
+         format,
+      }) => {
+         let mut session_path = match *name {
+            Some(ref name) => named_session_file(name)?,
+            None => data_file()?,
+         };
+         if let Some(generation) = from_backup {
+            session_path = storage::backup_path(&session_path, usize::from(generation));
+         }
+         if !session_path.exists() {
+            eyre::bail!("No session file at {}", session_path.display());
+         }
+         if dry_run {
+            let windows = load_session_windows(&config, &session_path)?;
+            return restore::print_plan(&config, &windows, format);
+         }
+         restore_session(&config, &session_path)?;
+         Ok(())
+      },
+      Some(Command::List) => {
+         for name in named_sessions()? {
+            println!("{name}");
+         }
+         Ok(())
+      },
+      Some(Command::Delete { ref name }) => {
+         let session_path = named_session_file(name)?;
+         if !session_path.exists() {
+            eyre::bail!("No session named '{name}' exists");
+         }
+         storage::remove(&session_path).wrap_err_with(|| {
+            format!(
+               "Failed to delete session file: {}",
+               session_path.display()
+            )
+         })?;
+         info!("deleted session '{name}'");
+         Ok(())
+      },
+      None => run_daemon(&args, &config),
+   }
+}
+
+/// Restore the last session, then keep it up to date until terminated
+fn run_daemon(args: &Args, config: &Config) -> eyre::Result<()> {
    let session_path = data_file()?;
    let term = Arc::new(AtomicBool::new(false));
    let manual_save = Arc::new(AtomicBool::new(false));
@@ -828,19 +774,44 @@ fn main() -> eyre::Result<()> {
    flag::register(SIGUSR1, Arc::clone(&manual_save))?;
 
    info!("starting nirinit-manager");
-   restore_session(&config, &session_path)?;
+   let mut late_windows = restore_session(config, &session_path)?;
 
-   info!("starting periodic save (interval: {}s)", args.save_interval);
+   let events = events::subscribe().wrap_err("Failed to subscribe to Niri's event stream")?;
+   let mut state = SessionState::default();
+   let debounce = Duration::from_secs(args.save_debounce);
+
+   if args.save_interval.is_some() {
+      warn!("--save-interval is deprecated and ignored, use --save-debounce instead");
+   }
+
+   info!(
+      "watching layout changes (saving after {}s of inactivity)",
+      args.save_debounce
+   );
    info!("send SIGUSR1 (pkill -USR1 nirinit) to trigger manual save");
-   let mut last_save = Instant::now();
 
    while !term.load(Ordering::Relaxed) {
-      thread::sleep(Duration::from_millis(100));
+      match events.recv_timeout(Duration::from_millis(100)) {
+         Ok(event) => state.apply(event),
+         Err(RecvTimeoutError::Timeout) => {},
+         Err(RecvTimeoutError::Disconnected) => {
+            error!("lost connection to Niri's event stream");
+            break;
+         },
+      }
+
+      // Place the windows that didn't appear during the restore once they do
+      if late_windows
+         .as_mut()
+         .is_some_and(|late_windows| late_windows.adopt(&state))
+      {
+         late_windows = None;
+      }
 
       // Check for manual save signal (SIGUSR1)
       if manual_save.swap(false, Ordering::Relaxed) {
          info!("received SIGUSR1, saving session...");
-         if let Err(report) = save_session(&session_path, &config) {
+         if let Err(report) = save_state(&session_path, config, &mut state) {
             error!("failed to save session: {report}");
             // Send failure notification
             let _ = std::process::Command::new("notify-send")
@@ -853,20 +824,18 @@ fn main() -> eyre::Result<()> {
                .args(["-t", "2000", "-a", "nirinit", "nirinit", "✅ Session 已保存"])
                .spawn();
          }
-         last_save = Instant::now();
       }
 
-      // Periodic auto-save
-      if last_save.elapsed() >= Duration::from_secs(args.save_interval) {
-         if let Err(report) = save_session(&session_path, &config) {
-            error!("failed to save session: {report}");
-         }
-         last_save = Instant::now();
+      // Save once the layout has stopped changing
+      if state.is_settled(debounce)
+         && let Err(report) = save_state(&session_path, config, &mut state)
+      {
+         error!("failed to save session: {report}");
       }
    }
 
    info!("shutting down...");
-   if let Err(report) = save_session(&session_path, &config) {
+   if let Err(report) = save_state(&session_path, config, &mut state) {
       error!("error saving final session: {report}");
    }
    info!("shutdown complete");
@@ -0,0 +1,134 @@
+use std::process::Command;
+
+use serde::{
+   Deserialize,
+   Serialize,
+};
+
+use crate::{
+   process,
+   shell,
+};
+
+/// A terminal multiplexer
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MultiplexerKind {
+   Tmux,
+   Zellij,
+   Screen,
+}
+
+/// A terminal multiplexer session a terminal window was attached to
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Multiplexer {
+   pub kind:    MultiplexerKind,
+   /// Name of the session, if it could be found out
+   pub session: Option<String>,
+}
+
+impl Multiplexer {
+   /// Find out which multiplexer session the client process `pid` is attached
+   /// to, if it is a multiplexer client at all.
+   pub fn detect(pid: i32) -> Option<Self> {
+      let comm = process::comm(pid)?;
+      let cmdline = process::cmdline(pid).unwrap_or_default();
+
+      let (kind, session) = if comm.starts_with("tmux") {
+         // tmux overwrites the command line of its client, so ask the server
+         (MultiplexerKind::Tmux, tmux_client_session(pid))
+      } else if comm == "zellij" {
+         let session = option_value(&cmdline, &["-s", "--session"])
+            .or_else(|| subcommand_arg(&cmdline, &["attach", "a"]));
+         (MultiplexerKind::Zellij, session)
+      } else if comm == "screen" {
+         let session = option_value(&cmdline, &["-S", "-r", "-x", "-R", "-DR", "-dr"]);
+         (MultiplexerKind::Screen, session)
+      } else {
+         return None;
+      };
+
+      let session = session.filter(|session| shell::is_valid_session_name(session));
+      Some(Self { kind, session })
+   }
+
+   /// Whether the session can be reattached to. Without its name, tmux would
+   /// attach to whichever session was used last, so a tmux session is only
+   /// reattached to by name.
+   pub fn can_reattach(&self) -> bool {
+      self.session.is_some() || self.kind != MultiplexerKind::Tmux
+   }
+
+   /// Shell command that succeeds once the session exists. tmux-continuum may
+   /// still be restoring the session when the terminal is relaunched, so it is
+   /// waited for before giving up on it.
+   pub fn session_check(&self) -> Option<String> {
+      match (self.kind, self.session.as_deref()) {
+         (MultiplexerKind::Tmux, Some(session)) => {
+            Some(format!("tmux has-session -t {}", shell_words::quote(session)))
+         },
+         _ => None,
+      }
+   }
+
+   /// Shell script that reattaches to the session, or starts a new one if it
+   /// no longer exists
+   pub fn attach_script(&self) -> String {
+      let session = self.session.as_deref().map(shell_words::quote);
+      match (self.kind, session) {
+         (MultiplexerKind::Tmux, Some(session)) => {
+            format!("tmux attach -t {session} || tmux new-session -s {session}")
+         },
+         (MultiplexerKind::Tmux, None) => "tmux attach || tmux new-session".to_owned(),
+         (MultiplexerKind::Zellij, Some(session)) => format!("zellij attach --create {session}"),
+         (MultiplexerKind::Zellij, None) => "zellij attach || zellij".to_owned(),
+         (MultiplexerKind::Screen, Some(session)) => format!("screen -D -R {session}"),
+         (MultiplexerKind::Screen, None) => "screen -D -R".to_owned(),
+      }
+   }
+}
+
+/// The session of a tmux client, as reported by the tmux server
+fn tmux_client_session(pid: i32) -> Option<String> {
+   let output = Command::new("tmux")
+      .args(["list-clients", "-F", "#{client_pid} #{session_name}"])
+      .output()
+      .ok()?;
+   if !output.status.success() {
+      return None;
+   }
+
+   String::from_utf8_lossy(&output.stdout)
+      .lines()
+      .find_map(|line| {
+         let (client, session) = line.split_once(' ')?;
+         (client.parse() == Ok(pid)).then(|| session.to_owned())
+      })
+}
+
+/// The value following any of `options` on a command line, e.g. `name` in
+/// `zellij -s name`
+fn option_value(cmdline: &[String], options: &[&str]) -> Option<String> {
+   cmdline.windows(2).find_map(|pair| {
+      match *pair {
+         [ref option, ref value]
+            if options.contains(&option.as_str()) && !value.starts_with('-') =>
+         {
+            Some(value.clone())
+         },
+         _ => None,
+      }
+   })
+}
+
+/// The first argument to any of `subcommands`, e.g. `name` in
+/// `zellij attach name`
+fn subcommand_arg(cmdline: &[String], subcommands: &[&str]) -> Option<String> {
+   let position = cmdline
+      .iter()
+      .position(|arg| subcommands.contains(&arg.as_str()))?;
+   cmdline[position + 1..]
+      .iter()
+      .find(|arg| !arg.starts_with('-'))
+      .cloned()
+}
@@ -0,0 +1,86 @@
+use std::fmt;
+
+use globset::{
+   Glob,
+   GlobMatcher,
+};
+use regex::Regex;
+use serde::{
+   Deserialize,
+   Deserializer,
+   de,
+};
+
+/// A pattern from the config that app ids and such are matched against.
+///
+/// Patterns written as `/regex/` are regular expressions, anything else is a
+/// glob like `jetbrains-*`.
+#[derive(Clone)]
+pub enum Pattern {
+   Glob(GlobMatcher),
+   Regex(Regex),
+}
+
+impl Pattern {
+   pub fn parse(pattern: &str) -> Result<Self, String> {
+      let regex = pattern
+         .strip_prefix('/')
+         .and_then(|pattern| pattern.strip_suffix('/'));
+      regex.map_or_else(
+         || {
+            Glob::new(pattern)
+               .map(|glob| Self::Glob(glob.compile_matcher()))
+               .map_err(|err| format!("invalid glob `{pattern}`: {err}"))
+         },
+         |regex| {
+            Regex::new(regex)
+               .map(Self::Regex)
+               .map_err(|err| format!("invalid regex `{regex}`: {err}"))
+         },
+      )
+   }
+
+   pub fn is_match(&self, text: &str) -> bool {
+      match *self {
+         Self::Glob(ref glob) => glob.is_match(text),
+         Self::Regex(ref regex) => regex.is_match(text),
+      }
+   }
+}
+
+impl fmt::Display for Pattern {
+   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+      match *self {
+         Self::Glob(ref glob) => write!(f, "{}", glob.glob()),
+         Self::Regex(ref regex) => write!(f, "/{regex}/"),
+      }
+   }
+}
+
+impl<'de> Deserialize<'de> for Pattern {
+   fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+      let pattern = String::deserialize(deserializer)?;
+      Self::parse(&pattern).map_err(de::Error::custom)
+   }
+}
+
+/// Deserialize an optional regular expression, for use with
+/// `#[serde(deserialize_with)]`
+pub fn deserialize_regex<'de, D: Deserializer<'de>>(
+   deserializer: D,
+) -> Result<Option<Regex>, D::Error> {
+   Option::<String>::deserialize(deserializer)?
+      .map(|regex| Regex::new(&regex).map_err(de::Error::custom))
+      .transpose()
+}
+
+/// Deserialize a list of regular expressions, for use with
+/// `#[serde(deserialize_with)]`
+pub fn deserialize_regexes<'de, D: Deserializer<'de>>(
+   deserializer: D,
+) -> Result<Vec<Regex>, D::Error> {
+   Vec::<String>::deserialize(deserializer)?
+      .iter()
+      .map(|regex| Regex::new(regex).map_err(de::Error::custom))
+      .collect()
+}
@@ -0,0 +1,134 @@
+use std::{
+   collections::HashMap,
+   fs,
+   iter,
+   path::PathBuf,
+   str,
+};
+
+/// How far up the process tree to look before giving up
+const MAX_ANCESTRY_DEPTH: usize = 16;
+
+/// Length of the longest process name in `/proc/<pid>/comm`
+const MAX_COMM_LEN: usize = 15;
+
+/// Index of the parent PID among the fields of `/proc/<pid>/stat` that follow
+/// the command name
+const STAT_PPID: usize = 1;
+
+/// Index of the terminal's foreground process group, see [`STAT_PPID`]
+const STAT_TPGID: usize = 5;
+
+/// A numeric field of `/proc/<pid>/stat`, counted from the field after the
+/// command name.
+fn stat_field(pid: i32, field: usize) -> Option<i32> {
+   let stat = fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+   // The command name is in parentheses and may contain spaces, so the fields
+   // are only split after the closing parenthesis: `state ppid ...`
+   let (_, fields) = stat.rsplit_once(')')?;
+   fields.split_whitespace().nth(field)?.parse().ok()
+}
+
+/// Parent PID of a process, read from `/proc/<pid>/stat`.
+pub fn parent_pid(pid: i32) -> Option<i32> {
+   stat_field(pid, STAT_PPID)
+}
+
+/// PIDs of all processes
+fn pids() -> impl Iterator<Item = i32> {
+   fs::read_dir("/proc")
+      .into_iter()
+      .flatten()
+      .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse::<i32>().ok())
+}
+
+/// Parent-child relations of all processes, as of when it was taken
+pub struct ProcessTree {
+   children: HashMap<i32, Vec<i32>>,
+}
+
+impl ProcessTree {
+   pub fn snapshot() -> Self {
+      let mut pids = pids().collect::<Vec<_>>();
+      pids.sort_unstable();
+
+      let mut children = HashMap::<i32, Vec<i32>>::new();
+      for pid in pids {
+         if let Some(parent) = parent_pid(pid) {
+            children.entry(parent).or_default().push(pid);
+         }
+      }
+      Self { children }
+   }
+
+   /// Direct children of a process, oldest first.
+   pub fn children(&self, pid: i32) -> &[i32] {
+      self.children.get(&pid).map_or(&[], Vec::as_slice)
+   }
+
+   /// The foreground process of the terminal that a child of `pid` is attached
+   /// to, e.g. the program running in the shell of a terminal emulator. GUI
+   /// apps have no terminal, so this is `None` for them.
+   pub fn foreground_process(&self, pid: i32) -> Option<i32> {
+      // The newest child is most likely the terminal's active tab or pane
+      self
+         .children(pid)
+         .iter()
+         .rev()
+         .find_map(|&child| stat_field(child, STAT_TPGID).filter(|&tpgid| tpgid > 0))
+   }
+}
+
+/// Name of the executable of a process, read from `/proc/<pid>/comm`. Programs
+/// may change it, e.g. tmux calls its client `tmux: client`.
+pub fn comm(pid: i32) -> Option<String> {
+   let comm = fs::read_to_string(format!("/proc/{pid}/comm")).ok()?;
+   Some(comm.trim_end().to_owned())
+}
+
+/// Whether a process with this name is running. The kernel keeps only the
+/// first 15 bytes of the name in `/proc/<pid>/comm`, so longer names are
+/// compared by those.
+pub fn is_running(name: &str) -> bool {
+   let name = name.get(..MAX_COMM_LEN).unwrap_or(name);
+   pids().any(|pid| comm(pid).as_deref() == Some(name))
+}
+
+/// Working directory of a process, read from `/proc/<pid>/cwd`.
+pub fn cwd(pid: i32) -> Option<PathBuf> {
+   fs::read_link(format!("/proc/{pid}/cwd")).ok()
+}
+
+/// Command line of a process, read from `/proc/<pid>/cmdline`.
+pub fn cmdline(pid: i32) -> Option<Vec<String>> {
+   let cmdline = fs::read(format!("/proc/{pid}/cmdline")).ok()?;
+   // Arguments are terminated, rather than separated, by NUL bytes
+   let cmdline = cmdline.strip_suffix(&[0]).unwrap_or(&cmdline);
+   if cmdline.is_empty() {
+      return None;
+   }
+   cmdline
+      .split(|&byte| byte == 0)
+      .map(|arg| str::from_utf8(arg).ok().map(str::to_owned))
+      .collect()
+}
+
+/// The process itself, followed by its parent, grandparent and so on.
+pub fn ancestry(pid: i32) -> impl Iterator<Item = i32> {
+   iter::successors(Some(pid), |&pid| parent_pid(pid))
+      .take_while(|&pid| pid > 1)
+      .take(MAX_ANCESTRY_DEPTH)
+}
+
+/// Value of an environment variable of a process, read from
+/// `/proc/<pid>/environ`.
+pub fn env_var(pid: i32, name: &str) -> Option<String> {
+   let environ = fs::read(format!("/proc/{pid}/environ")).ok()?;
+   environ
+      .split(|&byte| byte == 0)
+      .filter_map(|entry| str::from_utf8(entry).ok())
+      .find_map(|entry| {
+         let (key, value) = entry.split_once('=')?;
+         (key == name).then(|| value.to_owned())
+      })
+}
@@ -0,0 +1,1424 @@
+use std::{
+   collections::{
+      HashMap,
+      HashSet,
+   },
+   iter,
+   mem,
+   ops::Range,
+   path::PathBuf,
+   process::id as process_id,
+   sync::mpsc::{
+      Receiver,
+      RecvTimeoutError,
+   },
+   time::{
+      Duration,
+      Instant,
+   },
+};
+
+use clap::ValueEnum;
+use color_eyre::eyre;
+use eyre::Context as _;
+use log::{
+   debug,
+   error,
+   info,
+   warn,
+};
+use niri_ipc::{
+   Action,
+   ColumnDisplay,
+   Event,
+   PositionChange,
+   Reply,
+   Request,
+   Response,
+   SizeChange,
+   Window,
+   WorkspaceReferenceArg,
+   socket::Socket,
+};
+use serde::Serialize;
+
+use crate::{
+   Config,
+   NiriError,
+   events::{
+      self,
+      SessionState,
+   },
+   handlers::{
+      AppHandler,
+      Launch,
+      Registry,
+   },
+   launch::{
+      self,
+      LaunchEntry,
+      WaitFor,
+   },
+   process,
+   session::SessionWindow,
+};
+
+/// How long to wait for a spawned window to appear by default
+const DEFAULT_SPAWN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How many windows may be spawned ahead of the one being placed by default
+const DEFAULT_PARALLEL_SPAWNS: usize = 8;
+
+/// How long to keep watching for windows that didn't appear during the
+/// restore by default
+const DEFAULT_GRACE_PERIOD: Duration = Duration::from_mins(1);
+
+/// How long a new window may keep an empty title before it is matched on its
+/// app_id alone
+const TITLE_GRACE: Duration = Duration::from_secs(1);
+
+/// How often unmatched windows are reconsidered while waiting for events by
+/// default
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How often a `wait_for` condition is checked
+const CONDITION_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Environment variable that tags every spawned process with the session entry
+/// it was spawned for. It is inherited by child processes, so a window can be
+/// traced back to its spawn through the process tree.
+const SPAWN_ID_VAR: &str = "NIRINIT_SPAWN_ID";
+
+/// Runs the command following the directory in that directory. Unlike
+/// `env -C`, this works with any `env`, such as busybox's.
+const CHANGE_DIR: &str = r#"cd -- "$0" && exec "$@""#;
+
+/// Output format of `restore --dry-run`
+#[derive(Clone, Copy, Default, ValueEnum)]
+pub enum PlanFormat {
+   /// One line per window, for reading
+   #[default]
+   Table,
+   /// A JSON array, for scripts
+   Json,
+}
+
+/// One step of a restore plan: a window that would be spawned, and where it
+/// would be placed
+#[derive(Serialize)]
+struct PlanStep<'session> {
+   app_id:            &'session str,
+   command:           Vec<String>,
+   env:               Option<&'session HashMap<String, String>>,
+   cwd:               Option<PathBuf>,
+   output:            Option<&'session str>,
+   workspace_idx:     Option<u8>,
+   workspace_name:    Option<&'session str>,
+   column:            Option<usize>,
+   tile:              Option<usize>,
+   is_floating:       bool,
+   floating_position: Option<(f64, f64)>,
+   window_size:       Option<(i32, i32)>,
+   column_width:      Option<f64>,
+   column_display:    Option<ColumnDisplay>,
+   is_full_width:     bool,
+   after:             Vec<String>,
+   wait_for:          Option<WaitFor>,
+}
+
+/// Where a session window is in the restore process
+#[derive(Clone, Copy)]
+enum Status {
+   /// Not spawned yet
+   Queued,
+   /// Waiting for its `wait_for` condition before being spawned, since `since`
+   Waiting { since: Instant, checked: Instant },
+   /// Spawned, waiting for its window to appear
+   Spawned { at: Instant },
+   /// The window didn't appear in time. If it still does while the restore
+   /// runs, it is moved to where it belongs.
+   Late,
+   /// A newly opened window was matched to this entry
+   Matched { id: u64 },
+   /// Spawning failed
+   Failed,
+}
+
+/// A session window that is being restored
+struct Pending<'session> {
+   window:   &'session SessionWindow,
+   launch:   Launch<'session>,
+   handler:  &'session dyn AppHandler,
+   command:  Vec<String>,
+   /// Entries whose windows must have appeared, or failed to, before this one
+   /// is spawned
+   after:    Vec<usize>,
+   /// Condition that must hold before this entry is spawned
+   wait_for: Option<WaitFor>,
+   /// How long to wait for the window to appear once spawned
+   timeout:  Duration,
+   /// Value of [`SPAWN_ID_VAR`] for this entry
+   spawn_id: String,
+   status:   Status,
+}
+
+/// A session window that didn't appear while the session was restored
+struct LateWindow {
+   window:   SessionWindow,
+   /// Value of [`SPAWN_ID_VAR`] it was spawned with
+   spawn_id: String,
+}
+
+/// Windows that didn't appear while the session was restored. The daemon keeps
+/// watching for them for `[restore] grace_period`, and moves them to where they
+/// were in the session when they show up.
+pub struct LateWindows {
+   windows:  Vec<LateWindow>,
+   /// Windows that were already open when the restore finished, or were
+   /// matched since
+   known:    HashSet<u64>,
+   /// When to stop watching
+   deadline: Instant,
+   socket:   Socket,
+}
+
+/// State of a restore in progress
+struct Restore<'session> {
+   events:        Receiver<Event>,
+   state:         SessionState,
+   socket:        Socket,
+   pending:       Vec<Pending<'session>>,
+   /// Windows that existed before the restore, or were matched to an entry.
+   /// Each window is consumed by at most one entry.
+   claimed:       HashSet<u64>,
+   /// New windows that weren't matched yet, and when they were first seen
+   unmatched:     HashMap<u64, Instant>,
+   /// Entries whose window was matched after they timed out or were held, and
+   /// which still need to be placed
+   late:          Vec<usize>,
+   /// Entries whose turn to be placed came while they were still held back by
+   /// `after` or `wait_for`. They are spawned once ready and placed as soon as
+   /// their window appears, like late ones.
+   held:          Vec<usize>,
+   /// Windows that were placed where they were in the session
+   placed:        Vec<u64>,
+   /// How often unmatched windows are reconsidered while waiting for events
+   poll_interval: Duration,
+}
+
+/// Restore the given session windows, in order.
+///
+/// Windows are spawned ahead of time, up to `[restore] parallel` at once, and
+/// matched to the windows that Niri reports opening over its event stream.
+/// They are still placed one after another in session order, so columns and
+/// tiles end up in the same order as before, but the total restore time is
+/// bounded by the slowest application rather than the sum of all of them.
+///
+/// Entries with `after` or `wait_for` in the config are held back until the
+/// windows they come after have appeared and their condition holds, without
+/// holding up the entries after them. Windows
+/// that take longer than their `timeout` to appear are still moved to their
+/// workspace if they show up before the restore is done, and are returned to
+/// be watched for afterwards otherwise.
+pub fn restore_windows(
+   config: &Config,
+   windows: &[SessionWindow],
+) -> eyre::Result<Option<LateWindows>> {
+   let events = events::subscribe().wrap_err("Failed to subscribe to Niri's event stream")?;
+   let mut state = SessionState::default();
+   while !state.is_ready() {
+      let event = events
+         .recv()
+         .wrap_err("Niri's event stream closed before sending the current layout")?;
+      state.apply(event);
+   }
+
+   let registry = Registry::new(config);
+   let default_timeout = config
+      .restore
+      .timeout
+      .and_then(|timeout| Duration::try_from_secs_f64(timeout).ok())
+      .unwrap_or(DEFAULT_SPAWN_TIMEOUT);
+   let mut pending = windows
+      .iter()
+      .enumerate()
+      .filter_map(|(idx, window)| {
+         let launch = launch_of(config, window)?;
+         let (handler, command) = registry.build_command(&launch);
+         let wait_for = wait_for_of(config, handler, &launch);
+         let timeout = config
+            .launch
+            .get(launch.app_id)
+            .and_then(LaunchEntry::timeout)
+            .unwrap_or(default_timeout);
+         Some(Pending {
+            window,
+            launch,
+            handler,
+            command,
+            after: Vec::new(),
+            wait_for,
+            timeout,
+            spawn_id: format!("{}:{idx}", process_id()),
+            status: Status::Queued,
+         })
+      })
+      .collect::<Vec<_>>();
+   resolve_after(config, &mut pending);
+
+   let mut restore = Restore {
+      events,
+      claimed: state.windows().iter().map(|w| w.id).collect(),
+      state,
+      socket: Socket::connect().wrap_err("Failed to connect to Niri IPC socket")?,
+      pending,
+      unmatched: HashMap::new(),
+      late: Vec::new(),
+      held: Vec::new(),
+      placed: Vec::new(),
+      poll_interval: config
+         .restore
+         .poll_interval
+         .and_then(|interval| Duration::try_from_secs_f64(interval).ok())
+         .unwrap_or(DEFAULT_POLL_INTERVAL),
+   };
+
+   let parallel = config
+      .restore
+      .parallel
+      .unwrap_or(DEFAULT_PARALLEL_SPAWNS)
+      .max(1);
+
+   for idx in 0..restore.pending.len() {
+      let ahead = (idx + parallel).min(restore.pending.len());
+      restore.wait_for_window(idx, idx..ahead)?;
+      if matches!(
+         restore.pending[idx].status,
+         Status::Queued | Status::Waiting { .. }
+      ) {
+         restore.held.push(idx);
+      } else {
+         restore.place(idx, true)?;
+      }
+   }
+   while !restore.held.is_empty() {
+      restore.check_held()?;
+      restore.next_event(restore.poll_interval)?;
+   }
+
+   // Verify windows still exist after all operations
+   let settle = Instant::now() + Duration::from_millis(500);
+   while let Some(remaining) = settle.checked_duration_since(Instant::now()) {
+      restore.next_event(remaining)?;
+   }
+   for &id in &restore.placed {
+      if restore.state.window(id).is_none() {
+         warn!("window {id} disappeared immediately after restore!");
+      }
+   }
+
+   let grace_period = config
+      .restore
+      .grace_period
+      .and_then(|grace_period| Duration::try_from_secs_f64(grace_period).ok())
+      .unwrap_or(DEFAULT_GRACE_PERIOD);
+   let late = restore
+      .pending
+      .iter()
+      .filter(|entry| matches!(entry.status, Status::Late))
+      .map(|entry| {
+         LateWindow {
+            window:   entry.window.clone(),
+            spawn_id: entry.spawn_id.clone(),
+         }
+      })
+      .collect::<Vec<_>>();
+   if late.is_empty() || grace_period.is_zero() {
+      return Ok(None);
+   }
+
+   info!(
+      "watching for {} windows that haven't appeared yet for {}s",
+      late.len(),
+      grace_period.as_secs_f64()
+   );
+   Ok(Some(LateWindows {
+      windows:  late,
+      known:    restore.state.windows().iter().map(|w| w.id).collect(),
+      deadline: Instant::now() + grace_period,
+      socket:   restore.socket,
+   }))
+}
+
+impl LateWindows {
+   /// Move the late windows that have opened since the last call to where they
+   /// were in the session. Returns whether there is nothing left to watch for,
+   /// either because every window was placed or because the grace period is
+   /// over.
+   pub fn adopt(&mut self, state: &SessionState) -> bool {
+      if Instant::now() >= self.deadline {
+         for late in &self.windows {
+            warn!(
+               "window for {} never appeared, giving up on it",
+               late.window.app_id.as_deref().unwrap_or("unknown")
+            );
+         }
+         return true;
+      }
+
+      for window in state.windows() {
+         if self.known.contains(&window.id) {
+            continue;
+         }
+
+         let spawn_id = window.pid.and_then(spawn_id_of);
+         let Some(idx) = find_late(&self.windows, &window, spawn_id.as_deref()) else {
+            continue;
+         };
+
+         let late = self.windows.remove(idx);
+         self.known.insert(window.id);
+         info!(
+            "window for {} appeared after the restore, moving it to its workspace",
+            late.window.app_id.as_deref().unwrap_or("unknown")
+         );
+         if let Err(report) = place_window(
+            &mut self.socket,
+            &late.window,
+            &window,
+            state.focused_window(),
+            false,
+         ) {
+            error!("failed to place late window {}: {report}", window.id);
+         }
+      }
+
+      self.windows.is_empty()
+   }
+}
+
+/// Find the late window a new window belongs to, given the spawn id in its
+/// process' environment.
+///
+/// Once the restore is over, the user opens windows of the same apps, whose
+/// titles often share words with the saved ones, like the app name. So a window
+/// only counts as a late one if it was spawned for it, or has exactly the title
+/// it was saved with.
+fn find_late(
+   late_windows: &[LateWindow],
+   window: &Window,
+   spawn_id: Option<&str>,
+) -> Option<usize> {
+   let app_id = window.app_id.as_deref()?;
+   let late_windows = late_windows
+      .iter()
+      .enumerate()
+      .filter(|&(_, late)| late.window.app_id.as_deref() == Some(app_id))
+      .collect::<Vec<_>>();
+
+   let title = window.title.as_deref().filter(|title| !title.is_empty());
+   late_windows
+      .iter()
+      .find(|&&(_, late)| spawn_id == Some(late.spawn_id.as_str()))
+      .or_else(|| {
+         late_windows
+            .iter()
+            .find(|&&(_, late)| title.is_some() && late.window.title.as_deref() == title)
+      })
+      .map(|&(idx, _)| idx)
+}
+
+/// Print the windows that [`restore_windows`] would spawn, in order, and where
+/// they would be placed, without contacting Niri.
+pub fn print_plan(
+   config: &Config,
+   windows: &[SessionWindow],
+   format: PlanFormat,
+) -> eyre::Result<()> {
+   let registry = Registry::new(config);
+   let steps = windows
+      .iter()
+      .filter_map(|window| {
+         let launch = launch_of(config, window)?;
+         let (handler, command) = registry.build_command(&launch);
+         let after = config
+            .launch
+            .get(launch.app_id)
+            .map_or(&[][..], LaunchEntry::after)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+         let wait_for = wait_for_of(config, handler, &launch);
+         Some(PlanStep {
+            app_id: launch.app_id,
+            command,
+            env: launch.env,
+            cwd: launch.cwd,
+            output: window.workspace_output.as_deref(),
+            workspace_idx: window.workspace_idx,
+            workspace_name: window.workspace_name.as_deref(),
+            column: window.layout_position.map(|(column, _)| column),
+            tile: window.layout_position.map(|(_, tile)| tile),
+            is_floating: window.is_floating,
+            floating_position: window.floating_position,
+            window_size: window.window_size,
+            column_width: window.column_width,
+            column_display: window.column_display,
+            is_full_width: window.is_full_width,
+            after,
+            wait_for,
+         })
+      })
+      .collect::<Vec<_>>();
+
+   match format {
+      PlanFormat::Json => {
+         let json = serde_json::to_string_pretty(&steps).wrap_err("Failed to serialize plan")?;
+         println!("{json}");
+      },
+      PlanFormat::Table => print_plan_table(&steps),
+   }
+
+   Ok(())
+}
+
+fn print_plan_table(steps: &[PlanStep]) {
+   let header = [
+      "#",
+      "APP",
+      "OUTPUT",
+      "WORKSPACE",
+      "POSITION",
+      "SIZE",
+      "COMMAND",
+   ]
+   .map(str::to_owned);
+   let rows = steps
+      .iter()
+      .enumerate()
+      .map(|(idx, step)| {
+         let workspace = match (step.workspace_idx, step.workspace_name) {
+            (Some(idx), Some(name)) => format!("{idx} ({name})"),
+            (Some(idx), None) => idx.to_string(),
+            (None, Some(name)) => name.to_owned(),
+            (None, None) => "-".to_owned(),
+         };
+
+         let position = if step.is_floating {
+            step.floating_position.map_or_else(
+               || "floating".to_owned(),
+               |(x, y)| format!("floating at {x},{y}"),
+            )
+         } else {
+            match (step.column, step.tile) {
+               (Some(column), Some(tile)) => format!("column {column}, tile {tile}"),
+               _ => "-".to_owned(),
+            }
+         };
+
+         let mut size = if step.is_full_width {
+            "full width".to_owned()
+         } else if let Some(width) = step.column_width.filter(|_| !step.is_floating) {
+            format!("{:.0}%", width * 100.0)
+         } else {
+            step
+               .window_size
+               .map_or_else(|| "-".to_owned(), |(width, _)| format!("{width}px"))
+         };
+         if let Some((_, height)) = step.window_size {
+            size = format!("{size} x {height}px");
+         }
+         if step.column_display == Some(ColumnDisplay::Tabbed) {
+            size.push_str(", tabbed");
+         }
+
+         [
+            (idx + 1).to_string(),
+            step.app_id.to_owned(),
+            step.output.unwrap_or("-").to_owned(),
+            workspace,
+            position,
+            size,
+            shell_words::join(&step.command),
+         ]
+      })
+      .collect::<Vec<_>>();
+
+   let mut widths = header.clone().map(|cell| cell.len());
+   for row in &rows {
+      for (width, cell) in widths.iter_mut().zip(row) {
+         *width = (*width).max(cell.chars().count());
+      }
+   }
+
+   for row in iter::once(&header).chain(&rows) {
+      let line = row
+         .iter()
+         .zip(widths)
+         .map(|(cell, width)| format!("{cell:width$}"))
+         .collect::<Vec<_>>()
+         .join("  ");
+      println!("{}", line.trim_end());
+   }
+}
+
+/// What app handlers need to know to relaunch a session window, or `None` if
+/// the window can't be relaunched
+fn launch_of<'session>(
+   config: &'session Config,
+   window: &'session SessionWindow,
+) -> Option<Launch<'session>> {
+   let app_id = window.app_id.as_deref()?;
+   let entry = config.launch.get(app_id);
+
+   let exact = window.cmdline.as_ref().filter(|_| {
+      config
+         .restore
+         .exact
+         .iter()
+         .any(|pattern| pattern.is_match(app_id))
+   });
+   let command = match exact {
+      Some(cmdline) => cmdline.clone(),
+      None => launch::split_command_line(window.launch_command.as_deref()?),
+   };
+
+   // Start the app where it was running before, unless the config says
+   // otherwise, and as long as that directory still exists
+   let cwd = entry.and_then(LaunchEntry::cwd).or_else(|| {
+      window
+         .cwd
+         .as_ref()
+         .map(PathBuf::from)
+         .filter(|cwd| cwd.is_dir())
+   });
+
+   Some(Launch {
+      app_id,
+      title: window.title.as_deref(),
+      command,
+      env: entry.and_then(LaunchEntry::env),
+      cwd,
+      exact: exact.is_some(),
+      multiplexer: window.multiplexer.as_ref(),
+      cmdline: window.cmdline.as_deref(),
+   })
+}
+
+/// The condition to wait for before spawning a window: the one from the
+/// config, or else the one its handler asks for
+fn wait_for_of(config: &Config, handler: &dyn AppHandler, launch: &Launch) -> Option<WaitFor> {
+   config
+      .launch
+      .get(launch.app_id)
+      .and_then(LaunchEntry::wait_for)
+      .cloned()
+      .or_else(|| handler.wait_for(launch))
+}
+
+/// Fill in the entries each entry comes after, from `after` in the config.
+///
+/// Windows of the same app don't wait for each other, and an entry doesn't
+/// wait for another one that (through others) waits for it, so every entry is
+/// spawned eventually.
+fn resolve_after(config: &Config, pending: &mut [Pending]) {
+   let mut after = pending
+      .iter()
+      .map(|entry| {
+         let patterns = config
+            .launch
+            .get(entry.launch.app_id)
+            .map_or(&[][..], LaunchEntry::after);
+         pending
+            .iter()
+            .enumerate()
+            .filter(|&(_, other)| {
+               other.launch.app_id != entry.launch.app_id
+                  && patterns
+                     .iter()
+                     .any(|pattern| pattern.is_match(other.launch.app_id))
+            })
+            .map(|(idx, _)| idx)
+            .collect::<Vec<_>>()
+      })
+      .collect::<Vec<_>>();
+
+   for idx in 0..after.len() {
+      let deps = mem::take(&mut after[idx]);
+      let deps = deps
+         .into_iter()
+         .filter(|&dep| {
+            let cycle = waits_for(&after, dep, idx);
+            if cycle {
+               warn!(
+                  "{} and {} are set to come after each other, ignoring one of them",
+                  pending[idx].launch.app_id, pending[dep].launch.app_id
+               );
+            }
+            !cycle
+         })
+         .collect();
+      after[idx] = deps;
+   }
+
+   for (entry, after) in pending.iter_mut().zip(after) {
+      entry.after = after;
+   }
+}
+
+/// Whether entry `from` waits for entry `to`, directly or through others
+fn waits_for(after: &[Vec<usize>], from: usize, to: usize) -> bool {
+   let mut seen = vec![false; after.len()];
+   let mut stack = vec![from];
+   while let Some(idx) = stack.pop() {
+      if idx == to {
+         return true;
+      }
+      if !mem::replace(&mut seen[idx], true) {
+         stack.extend(&after[idx]);
+      }
+   }
+   false
+}
+
+impl Restore<'_> {
+   /// Spawn `pending[idx]` once the entries it comes after have their windows
+   /// and its `wait_for` condition holds, or has timed out. The entries it
+   /// comes after are spawned first if they weren't yet.
+   fn spawn_when_ready(&mut self, idx: usize) -> eyre::Result<()> {
+      let waiting = match self.pending[idx].status {
+         Status::Queued => None,
+         Status::Waiting { since, checked } => Some((since, checked)),
+         Status::Spawned { .. } | Status::Late | Status::Matched { .. } | Status::Failed => {
+            return Ok(());
+         },
+      };
+
+      let mut ready = true;
+      for dep in self.pending[idx].after.clone() {
+         self.spawn_when_ready(dep)?;
+         self.time_left(dep);
+         ready &= matches!(
+            self.pending[dep].status,
+            Status::Late | Status::Matched { .. } | Status::Failed
+         );
+      }
+      if !ready {
+         return Ok(());
+      }
+
+      if let Some(ref wait_for) = self.pending[idx].wait_for {
+         if waiting.is_some_and(|(_, checked)| checked.elapsed() < CONDITION_INTERVAL) {
+            return Ok(());
+         }
+         if !wait_for.holds() {
+            let now = Instant::now();
+            let since = waiting.map_or(now, |(since, _)| since);
+            if since.elapsed() < wait_for.timeout() {
+               if waiting.is_none() {
+                  debug!(
+                     "waiting for `{}` to be ready",
+                     self.pending[idx].command.join(" ")
+                  );
+               }
+               self.pending[idx].status = Status::Waiting {
+                  since,
+                  checked: now,
+               };
+               return Ok(());
+            }
+            warn!(
+               "`{}` is still not ready after {}s, spawning it anyway",
+               self.pending[idx].command.join(" "),
+               wait_for.timeout().as_secs_f64()
+            );
+         }
+      }
+
+      self.spawn(idx)
+   }
+
+   fn spawn(&mut self, idx: usize) -> eyre::Result<()> {
+      let entry = &mut self.pending[idx];
+
+      let mut command = vec![
+         "env".to_owned(),
+         format!("{SPAWN_ID_VAR}={}", entry.spawn_id),
+      ];
+      if let Some(env) = entry.launch.env {
+         let mut env = env.iter().collect::<Vec<_>>();
+         env.sort_unstable();
+         command.extend(env.into_iter().map(|(name, value)| format!("{name}={value}")));
+      }
+      if let Some(ref cwd) = entry.launch.cwd {
+         command.extend([
+            "sh".to_owned(),
+            "-c".to_owned(),
+            CHANGE_DIR.to_owned(),
+            cwd.to_string_lossy().into_owned(),
+         ]);
+      }
+      command.extend(entry.command.iter().cloned());
+
+      debug!("spawning command: {:?}", entry.command);
+      let reply = self
+         .socket
+         .send(Request::Action(Action::Spawn { command }))
+         .map_err(NiriError::Send)?;
+
+      entry.status = if matches!(reply, Reply::Ok(Response::Handled)) {
+         Status::Spawned { at: Instant::now() }
+      } else {
+         error!("failed to spawn command `{}`", entry.command.join(" "));
+         Status::Failed
+      };
+
+      Ok(())
+   }
+
+   /// Time left until the window of a spawned entry is given up on. Once it is
+   /// up, any new window of the app is taken as a last chance, and the entry is
+   /// late if there is none.
+   fn time_left(&mut self, idx: usize) -> Option<Duration> {
+      let Status::Spawned { at } = self.pending[idx].status else {
+         return None;
+      };
+
+      let timeout = self.pending[idx].timeout;
+      let remaining = (at + timeout).checked_duration_since(Instant::now());
+      if remaining.is_none() {
+         // Last chance: take any new window of the app, whatever its title
+         self.match_windows(true);
+         if matches!(self.pending[idx].status, Status::Spawned { .. }) {
+            warn!(
+               "window for `{}` did not appear within {}s, it is still moved to its workspace if \
+                it does",
+               self.pending[idx].command.join(" "),
+               timeout.as_secs_f64()
+            );
+            self.pending[idx].status = Status::Late;
+         }
+      }
+      remaining
+   }
+
+   /// Process events until the window of `pending[idx]` has appeared, or it
+   /// timed out, unless it is still held back. Entries in `ahead` and held
+   /// entries are spawned as soon as they are ready, and windows of other
+   /// spawned entries are matched along the way.
+   fn wait_for_window(&mut self, idx: usize, ahead: Range<usize>) -> eyre::Result<()> {
+      loop {
+         for next in ahead.clone() {
+            self.spawn_when_ready(next)?;
+         }
+         self.check_held()?;
+
+         let remaining = match self.pending[idx].status {
+            // Still held back by `after` or `wait_for`, it is placed once its
+            // window appears
+            Status::Queued | Status::Waiting { .. } => return Ok(()),
+            Status::Spawned { .. } => {
+               match self.time_left(idx) {
+                  Some(remaining) => remaining.min(self.poll_interval),
+                  None => return Ok(()),
+               }
+            },
+            Status::Late | Status::Matched { .. } | Status::Failed => return Ok(()),
+         };
+
+         self.next_event(remaining)?;
+      }
+   }
+
+   /// Spawn the held entries that are ready, and give up on the windows of
+   /// those that timed out
+   fn check_held(&mut self) -> eyre::Result<()> {
+      for idx in self.held.clone() {
+         self.spawn_when_ready(idx)?;
+         self.time_left(idx);
+      }
+      let pending = &self.pending;
+      self.held.retain(|&idx| {
+         matches!(
+            pending[idx].status,
+            Status::Queued | Status::Waiting { .. } | Status::Spawned { .. }
+         )
+      });
+      Ok(())
+   }
+
+   /// Wait up to `timeout` for the next event, then match new windows to
+   /// spawned entries and place those that showed up late.
+   fn next_event(&mut self, timeout: Duration) -> eyre::Result<()> {
+      match self.events.recv_timeout(timeout) {
+         Ok(event) => {
+            // Windows may get their app_id or title only after opening, so
+            // every change of an unclaimed window is a chance to match it
+            if let Event::WindowOpenedOrChanged { ref window } = event
+               && !self.claimed.contains(&window.id)
+            {
+               self.unmatched.entry(window.id).or_insert_with(Instant::now);
+            }
+            self.state.apply(event);
+         },
+         Err(RecvTimeoutError::Timeout) => {},
+         Err(RecvTimeoutError::Disconnected) => {
+            eyre::bail!("Lost connection to Niri's event stream")
+         },
+      }
+
+      self.match_windows(false);
+      for idx in mem::take(&mut self.late) {
+         info!(
+            "window for `{}` appeared after its turn, moving it to its workspace",
+            self.pending[idx].command.join(" ")
+         );
+         self.place(idx, false)?;
+      }
+      Ok(())
+   }
+
+   /// Move the window matched to `pending[idx]` to where it was in the
+   /// session, see [`place_window`]
+   fn place(&mut self, idx: usize, in_order: bool) -> eyre::Result<()> {
+      let entry = &self.pending[idx];
+      if let Status::Matched { id } = entry.status
+         && let Some(new_window) = self.state.window(id)
+      {
+         place_window(
+            &mut self.socket,
+            entry.window,
+            new_window,
+            self.state.focused_window(),
+            in_order,
+         )?;
+         entry
+            .handler
+            .after_place(&mut self.socket, &entry.launch, new_window);
+         self.placed.push(id);
+      }
+      Ok(())
+   }
+
+   /// Try to match every unmatched new window to a spawned session entry.
+   ///
+   /// With `force`, windows are matched even if their title doesn't tell
+   /// entries of the same app apart yet.
+   fn match_windows(&mut self, force: bool) {
+      let state = &self.state;
+      self.unmatched.retain(|&id, _| state.window(id).is_some());
+
+      let mut ids = self.unmatched.keys().copied().collect::<Vec<_>>();
+      ids.sort_unstable();
+
+      for id in ids {
+         let Some(window) = self.state.window(id) else {
+            continue;
+         };
+         let force = force || self.unmatched[&id].elapsed() >= TITLE_GRACE;
+         let Some(idx) = find_entry(&self.pending, window, force) else {
+            continue;
+         };
+
+         debug!(
+            "matched new window {id} to app_id={}, title={:?}",
+            self.pending[idx].launch.app_id, self.pending[idx].window.title
+         );
+         if matches!(self.pending[idx].status, Status::Late) || self.held.contains(&idx) {
+            self.late.push(idx);
+         }
+         self.pending[idx].status = Status::Matched { id };
+         self.claimed.insert(id);
+         self.unmatched.remove(&id);
+      }
+   }
+}
+
+/// A session entry that a new window may belong to
+struct Candidate<'entry> {
+   idx:      usize,
+   app_id:   &'entry str,
+   /// Title of the window when the session was saved
+   title:    &'entry str,
+   spawn_id: &'entry str,
+}
+
+/// Find the spawned session entry a new window belongs to, see
+/// [`find_candidate`].
+fn find_entry(pending: &[Pending], window: &Window, force: bool) -> Option<usize> {
+   let candidates = pending
+      .iter()
+      .enumerate()
+      .filter(|&(_, entry)| matches!(entry.status, Status::Spawned { .. } | Status::Late))
+      .map(|(idx, entry)| {
+         Candidate {
+            idx,
+            app_id: entry.launch.app_id,
+            title: entry.window.title.as_deref().unwrap_or_default(),
+            spawn_id: &entry.spawn_id,
+         }
+      })
+      .collect::<Vec<_>>();
+   let spawn_id = window.pid.and_then(spawn_id_of);
+   find_candidate(&candidates, window, spawn_id.as_deref(), force)
+}
+
+/// Find the session entry a new window belongs to, among `candidates`.
+///
+/// 1. If `spawn_id`, found on the window's process or one of its ancestors, is
+///    the spawn id of an entry, the window is that entry's.
+/// 2. Otherwise the window goes to the entry of the same app whose saved title
+///    is most similar to the window's title, earlier entries first.
+///
+/// Without `force`, a window with an empty title is not matched while several
+/// entries of its app are waiting, as apps usually set the title shortly after
+/// opening.
+fn find_candidate(
+   candidates: &[Candidate],
+   window: &Window,
+   spawn_id: Option<&str>,
+   force: bool,
+) -> Option<usize> {
+   let app_id = window.app_id.as_deref()?;
+   let candidates = candidates
+      .iter()
+      .filter(|candidate| candidate.app_id == app_id)
+      .collect::<Vec<_>>();
+
+   if let Some(spawn_id) = spawn_id
+      && let Some(candidate) = candidates
+         .iter()
+         .find(|candidate| candidate.spawn_id == spawn_id)
+   {
+      return Some(candidate.idx);
+   }
+
+   let title = window.title.as_deref().unwrap_or_default();
+   if candidates.len() > 1 && title.is_empty() && !force {
+      return None;
+   }
+
+   // `max_by` picks the last of equal elements, so iterate in reverse to prefer
+   // earlier entries on ties
+   candidates
+      .iter()
+      .rev()
+      .max_by(|left, right| {
+         title_similarity(left.title, title).total_cmp(&title_similarity(right.title, title))
+      })
+      .map(|candidate| candidate.idx)
+}
+
+/// The spawn id in the environment of a process or its closest ancestor
+fn spawn_id_of(pid: i32) -> Option<String> {
+   process::ancestry(pid).find_map(|pid| process::env_var(pid, SPAWN_ID_VAR))
+}
+
+/// Similarity of two titles between 0 and 1, as the share of words they have in
+/// common
+fn title_similarity(saved: &str, current: &str) -> f64 {
+   if saved == current {
+      return 1.0;
+   }
+
+   let words = |title: &str| {
+      title
+         .split(|ch: char| !ch.is_alphanumeric())
+         .filter(|word| !word.is_empty())
+         .map(str::to_lowercase)
+         .collect::<HashSet<_>>()
+   };
+   let (saved, current) = (words(saved), words(current));
+
+   let count = |words: usize| f64::from(u32::try_from(words).unwrap_or(u32::MAX));
+   let union = saved.union(&current).count();
+   if union == 0 {
+      return 0.0;
+   }
+   count(saved.intersection(&current).count()) / count(union)
+}
+
+/// Move a newly opened window to where it was in the session. If the window
+/// has to be focused on the way, focus goes back to `focused` afterwards.
+///
+/// Windows placed `in_order` follow the window above them in their column, so
+/// they are stacked into the column to their left. Windows placed out of order
+/// end up next to whatever column is active, so they keep a column of their
+/// own rather than join an unrelated one.
+fn place_window(
+   socket: &mut Socket,
+   window: &SessionWindow,
+   new_window: &Window,
+   focused: Option<u64>,
+   in_order: bool,
+) -> eyre::Result<()> {
+   let workspace_idx = window.workspace_idx;
+   let workspace_name = window.workspace_name.as_deref();
+   let workspace_output = window.workspace_output.as_deref();
+   let window_size = window.window_size;
+   let layout_position = window.layout_position;
+
+   info!(
+      "restoring window: app_id={}, workspace_name={:?}, workspace_idx={:?}, output={:?}, \
+       layout_pos={:?}",
+      new_window.app_id.as_deref().unwrap_or("unknown"),
+      workspace_name,
+      workspace_idx,
+      workspace_output,
+      layout_position
+   );
+
+   // Always use Index for workspace reference (Name doesn't work for unnamed
+   // workspaces)
+   let Some(idx) = workspace_idx else {
+      return Ok(());
+   };
+   let workspace_reference = WorkspaceReferenceArg::Index(idx);
+
+   if let Some(output) = workspace_output
+      && let Err(err) = socket.send(Request::Action(Action::MoveWindowToMonitor {
+         id:     Some(new_window.id),
+         output: output.to_owned(),
+      }))
+   {
+      warn!(
+         "failed to move window {}: {err}",
+         new_window
+            .app_id
+            .as_ref()
+            .map_or_else(String::new, |app_id| format!("(app_id: {app_id})")),
+      );
+   }
+
+   // Move window to the correct workspace
+   // This will automatically create the workspace if it doesn't exist
+   socket
+      .send(Request::Action(Action::MoveWindowToWorkspace {
+         window_id: Some(new_window.id),
+         reference: workspace_reference,
+         focus:     false,
+      }))
+      .map_err(NiriError::Send)?
+      .map_err(NiriError::Reply)?;
+
+   debug!(
+      "moved window {} to workspace {:?}",
+      new_window.id,
+      workspace_name.or(workspace_idx.map(|i| i.to_string()).as_deref())
+   );
+
+   // Set workspace name AFTER moving window (workspace now exists)
+   if let Some(name) = workspace_name {
+      info!("setting workspace {} name to '{}'", idx, name);
+      if let Err(err) = socket.send(Request::Action(Action::SetWorkspaceName {
+         name:      name.to_owned(),
+         workspace: Some(WorkspaceReferenceArg::Index(idx)),
+      })) {
+         warn!("failed to set workspace name '{}': {}", name, err);
+      }
+   }
+
+   // Floating windows (dialogs, pickers, picture-in-picture) are taken out of
+   // the scrolling layout before they are resized and positioned
+   if window.is_floating
+      && let Err(err) = socket.send(Request::Action(Action::MoveWindowToFloating {
+         id: Some(new_window.id),
+      }))
+   {
+      warn!("failed to make window {} floating: {err}", new_window.id);
+   }
+
+   // Windows that open floating by a window rule but were tiled in the session
+   // go back into the scrolling layout
+   if !window.is_floating
+      && new_window.is_floating
+      && let Err(err) = socket.send(Request::Action(Action::MoveWindowToTiling {
+         id: Some(new_window.id),
+      }))
+   {
+      warn!("failed to make window {} tiled: {err}", new_window.id);
+   }
+
+   // Handle vertical stacking within columns
+   // If tile > 1, this window should be stacked below another window in the same
+   // column. We consume it into the column to the left (which contains the
+   // previous tile).
+   if let Some((col, tile)) = layout_position {
+      if tile > 1 && !in_order {
+         debug!(
+            "window {} is at tile {} in column {}, but placed out of order, leaving it in a \
+             column of its own",
+            new_window.id, tile, col
+         );
+      } else if tile > 1 {
+         debug!(
+            "window {} is at tile {} in column {}, consuming into column to the left",
+            new_window.id, tile, col
+         );
+         // ConsumeOrExpelWindowLeft will merge this window into the column to its left
+         if let Err(err) = socket.send(Request::Action(Action::ConsumeOrExpelWindowLeft {
+            id: Some(new_window.id),
+         })) {
+            warn!(
+               "failed to consume window {} into column: {}",
+               new_window.id, err
+            );
+         }
+      }
+   }
+
+   // Column properties are restored once, by the topmost window of the column
+   let is_column_head = !window.is_floating && layout_position.is_none_or(|(_, tile)| tile == 1);
+   let is_tabbed = window.column_display == Some(ColumnDisplay::Tabbed);
+
+   // Prefer restoring the width as a proportion of the output, which survives
+   // changes to the resolution or scale. Full width is the proportion 100%,
+   // which unlike `MaximizeColumn` doesn't undo a column opened full width.
+   let width_change = if window.is_floating {
+      window_size.map(|(width, _)| SizeChange::SetFixed(width))
+   } else if window.is_full_width {
+      Some(SizeChange::SetProportion(100.0))
+   } else {
+      window
+         .column_width
+         .map(|width| SizeChange::SetProportion(width * 100.0))
+         .or_else(|| window_size.map(|(width, _)| SizeChange::SetFixed(width)))
+   };
+
+   if let Some(change) = width_change
+      && (window.is_floating || is_column_head)
+      && let Err(err) = socket.send(Request::Action(Action::SetWindowWidth {
+         id: Some(new_window.id),
+         change,
+      }))
+   {
+      warn!(
+         "failed to restore window width for {}: {err}",
+         new_window.app_id.as_deref().unwrap_or("unknown")
+      );
+   }
+
+   // Tabbed windows always take up the full column height
+   if let Some((_, height)) = window_size
+      && !is_tabbed
+      && let Err(err) = socket.send(Request::Action(Action::SetWindowHeight {
+         id:     Some(new_window.id),
+         change: SizeChange::SetFixed(height),
+      }))
+   {
+      warn!(
+         "failed to restore window height for {}: {err}",
+         new_window.app_id.as_deref().unwrap_or("unknown")
+      );
+   }
+
+   // Column display only applies to the focused column
+   if is_column_head && is_tabbed {
+      let mut actions = vec![
+         Action::FocusWindow { id: new_window.id },
+         Action::SetColumnDisplay {
+            display: ColumnDisplay::Tabbed,
+         },
+      ];
+      if let Some(id) = focused.filter(|&id| id != new_window.id) {
+         actions.push(Action::FocusWindow { id });
+      }
+
+      for action in actions {
+         if let Err(err) = socket.send(Request::Action(action)) {
+            warn!(
+               "failed to restore column layout for {}: {err}",
+               new_window.app_id.as_deref().unwrap_or("unknown")
+            );
+         }
+      }
+   }
+
+   if window.is_floating
+      && let Some((x, y)) = window.floating_position
+      && let Err(err) = socket.send(Request::Action(Action::MoveFloatingWindow {
+         id: Some(new_window.id),
+         x:  PositionChange::SetFixed(x),
+         y:  PositionChange::SetFixed(y),
+      }))
+   {
+      warn!(
+         "failed to restore floating position for {}: {err}",
+         new_window.app_id.as_deref().unwrap_or("unknown")
+      );
+   }
+
+   Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+   use niri_ipc::WindowLayout;
+   use serde_json::json;
+
+   use super::*;
+   use crate::session;
+
+   /// A window Niri reports, without a process
+   fn new_window(id: u64, app_id: &str, title: &str) -> Window {
+      Window {
+         id,
+         title: Some(title.to_owned()),
+         app_id: Some(app_id.to_owned()),
+         pid: None,
+         workspace_id: None,
+         is_focused: false,
+         is_floating: false,
+         is_urgent: false,
+         layout: WindowLayout {
+            pos_in_scrolling_layout:    None,
+            tile_size:                  (0.0, 0.0),
+            window_size:                (0, 0),
+            tile_pos_in_workspace_view: None,
+            window_offset_in_tile:      (0.0, 0.0),
+         },
+         focus_timestamp: None,
+      }
+   }
+
+   /// A window as it was saved in the session
+   fn saved_window(app_id: &str, title: &str) -> SessionWindow {
+      let data = json!([{
+         "id": 1,
+         "app_id": app_id,
+         "title": title,
+         "launch_command": app_id,
+         "is_focused": false,
+      }]);
+      session::parse(&data.to_string()).unwrap().windows.remove(0)
+   }
+
+   fn late_window(app_id: &str, title: &str, spawn_id: &str) -> LateWindow {
+      LateWindow {
+         window:   saved_window(app_id, title),
+         spawn_id: spawn_id.to_owned(),
+      }
+   }
+
+   #[test]
+   fn adopts_late_window_by_spawn_id() {
+      let late = [
+         late_window("firefox", "Inbox - Mozilla Firefox", "1:0"),
+         late_window("firefox", "News - Mozilla Firefox", "1:1"),
+      ];
+      let window = new_window(7, "firefox", "Mozilla Firefox");
+      assert_eq!(find_late(&late, &window, Some("1:1")), Some(1));
+      assert_eq!(find_late(&late, &window, Some("2:1")), None);
+   }
+
+   #[test]
+   fn adopts_late_window_with_same_title() {
+      let late = [late_window("firefox", "Inbox - Mozilla Firefox", "1:0")];
+      let window = new_window(7, "firefox", "Inbox - Mozilla Firefox");
+      assert_eq!(find_late(&late, &window, None), Some(0));
+      // Another app with the same title isn't it
+      let window = new_window(7, "chromium", "Inbox - Mozilla Firefox");
+      assert_eq!(find_late(&late, &window, None), None);
+   }
+
+   #[test]
+   fn ignores_windows_sharing_only_the_app_name() {
+      let late = [late_window("firefox", "Inbox - Mozilla Firefox", "1:0")];
+      let window = new_window(7, "firefox", "GitHub - Mozilla Firefox");
+      assert_eq!(find_late(&late, &window, None), None);
+      let window = new_window(8, "firefox", "");
+      assert_eq!(find_late(&late, &window, None), None);
+   }
+
+   fn candidate<'entry>(
+      idx: usize,
+      title: &'entry str,
+      spawn_id: &'entry str,
+   ) -> Candidate<'entry> {
+      Candidate {
+         idx,
+         app_id: "firefox",
+         title,
+         spawn_id,
+      }
+   }
+
+   #[test]
+   fn matches_spawn_id_before_title() {
+      let candidates = [
+         candidate(0, "Inbox - Mozilla Firefox", "1:0"),
+         candidate(1, "News - Mozilla Firefox", "1:1"),
+      ];
+      let window = new_window(7, "firefox", "Inbox - Mozilla Firefox");
+      assert_eq!(
+         find_candidate(&candidates, &window, Some("1:1"), false),
+         Some(1)
+      );
+      // An unknown spawn id falls back to the title
+      assert_eq!(
+         find_candidate(&candidates, &window, Some("2:1"), false),
+         Some(0)
+      );
+   }
+
+   #[test]
+   fn matches_most_similar_title() {
+      let candidates = [
+         candidate(0, "Inbox - Mozilla Firefox", "1:0"),
+         candidate(1, "GitHub - Mozilla Firefox", "1:1"),
+      ];
+      let window = new_window(7, "firefox", "GitHub - Mozilla Firefox");
+      assert_eq!(find_candidate(&candidates, &window, None, false), Some(1));
+      let window = new_window(7, "chromium", "GitHub - Mozilla Firefox");
+      assert_eq!(find_candidate(&candidates, &window, None, false), None);
+   }
+
+   #[test]
+   fn prefers_earlier_entries_on_ties() {
+      let candidates = [
+         candidate(0, "Mozilla Firefox", "1:0"),
+         candidate(1, "Mozilla Firefox", "1:1"),
+         candidate(2, "Mozilla Firefox", "1:2"),
+      ];
+      let window = new_window(7, "firefox", "Mozilla Firefox");
+      assert_eq!(find_candidate(&candidates, &window, None, false), Some(0));
+      assert_eq!(
+         find_candidate(&candidates[1..], &window, None, false),
+         Some(1)
+      );
+   }
+
+   #[test]
+   fn holds_off_windows_without_title() {
+      let candidates = [
+         candidate(0, "Inbox - Mozilla Firefox", "1:0"),
+         candidate(1, "News - Mozilla Firefox", "1:1"),
+      ];
+      let window = new_window(7, "firefox", "");
+      assert_eq!(find_candidate(&candidates, &window, None, false), None);
+      assert_eq!(find_candidate(&candidates, &window, None, true), Some(0));
+      // A spawn id is enough to match it
+      assert_eq!(
+         find_candidate(&candidates, &window, Some("1:1"), false),
+         Some(1)
+      );
+      // With a single entry waiting, there is nothing to confuse it with
+      assert_eq!(
+         find_candidate(&candidates[1..], &window, None, false),
+         Some(1)
+      );
+   }
+
+   #[test]
+   fn measures_title_similarity_by_shared_words() {
+      assert!((title_similarity("Mozilla Firefox", "Mozilla Firefox") - 1.0).abs() < f64::EPSILON);
+      assert!((title_similarity("", "") - 1.0).abs() < f64::EPSILON);
+      assert!(title_similarity("", "Mozilla Firefox").abs() < f64::EPSILON);
+      // Case and punctuation don't count
+      let similarity = title_similarity("Inbox - Mozilla Firefox", "inbox: mozilla firefox");
+      assert!((similarity - 1.0).abs() < f64::EPSILON);
+      // 2 shared words out of 4
+      let similarity = title_similarity("Inbox - Mozilla Firefox", "GitHub - Mozilla Firefox");
+      assert!((similarity - 0.5).abs() < f64::EPSILON);
+   }
+}
@@ -0,0 +1,420 @@
+use std::time::{
+   SystemTime,
+   UNIX_EPOCH,
+};
+
+use color_eyre::eyre;
+use eyre::Context as _;
+use niri_ipc::ColumnDisplay;
+use serde::{
+   Deserialize,
+   Serialize,
+};
+use serde_json::{
+   Map,
+   Value,
+};
+
+use crate::multiplexer::Multiplexer;
+
+/// Version of the session file schema written by this build.
+///
+/// Bump this and append a step to [`MIGRATIONS`] whenever the schema changes.
+pub const CURRENT_VERSION: u32 = 5;
+
+/// Upgrade steps between schema versions. `MIGRATIONS[n]` turns a version `n`
+/// session into a version `n + 1` session.
+const MIGRATIONS: &[fn(Value) -> eyre::Result<Value>] = &[
+   migrate_v0_to_v1,
+   migrate_v1_to_v2,
+   migrate_v2_to_v3,
+   migrate_v3_to_v4,
+   migrate_v4_to_v5,
+];
+
+/// A session file, as stored on disk
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SessionFile {
+   /// Schema version of this file
+   pub version:  u32,
+   /// Where and when the session was saved
+   pub metadata: SessionMetadata,
+   /// The saved windows
+   pub windows:  Vec<SessionWindow>,
+}
+
+/// Information about the machine and compositor a session was saved on
+#[derive(Default, Serialize, Deserialize)]
+pub struct SessionMetadata {
+   /// Hostname of the machine
+   pub hostname:     Option<String>,
+   /// Time of the save, in seconds since the Unix epoch
+   pub timestamp:    Option<u64>,
+   /// Version of Niri, as reported by `Request::Version`
+   pub niri_version: Option<String>,
+}
+
+/// Window data for session persistence
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionWindow {
+   pub id:                u64,
+   /// The application id of the window, see <https://wayland-book.com/xdg-shell-basics/xdg-toplevel.html>
+   pub app_id:            Option<String>,
+   /// The window title (used for extracting project paths for IDEs like
+   /// PyCharm)
+   pub title:             Option<String>,
+   /// The launch command to spawn this window (mapped from `app_id` via config,
+   /// otherwise `app_id` if no mapping exists)
+   pub launch_command:    Option<String>,
+   /// Index of the workspace on the corresponding monitor
+   pub workspace_idx:     Option<u8>,
+   /// Name of the workspace, in case of a named workspace
+   pub workspace_name:    Option<String>,
+   /// Output the workspace is on
+   pub workspace_output:  Option<String>,
+   /// Whether the window is focused or not
+   pub is_focused:        bool,
+   /// Window size (width, height) in logical pixels
+   pub window_size:       Option<(i32, i32)>,
+   /// Position in the scrolling layout: (column index, tile index within
+   /// column) Column index starts from 1 (leftmost), tile index starts from
+   /// 1 (topmost) Used to restore the exact layout including vertical
+   /// stacking within columns
+   pub layout_position:   Option<(usize, usize)>,
+   /// Whether the window is in the floating layout
+   pub is_floating:       bool,
+   /// Position of a floating window within the workspace view, in logical
+   /// pixels
+   pub floating_position: Option<(f64, f64)>,
+   /// Width of the window's column as the proportion Niri resolves to that
+   /// width, given the gaps and struts of its layout
+   pub column_width:      Option<f64>,
+   /// Whether the window's column shows its windows stacked or as tabs
+   pub column_display:    Option<ColumnDisplay>,
+   /// Whether the window's column spans the whole output
+   pub is_full_width:     bool,
+   /// Working directory of the window's process, or of the program running in
+   /// it for terminals
+   pub cwd:               Option<String>,
+   /// Command line the window's process was started with
+   pub cmdline:           Option<Vec<String>>,
+   /// Multiplexer session a terminal window was attached to
+   pub multiplexer:       Option<Multiplexer>,
+}
+
+impl SessionFile {
+   pub const fn new(windows: Vec<SessionWindow>, metadata: SessionMetadata) -> Self {
+      Self {
+         version: CURRENT_VERSION,
+         metadata,
+         windows,
+      }
+   }
+}
+
+impl SessionMetadata {
+   /// Metadata describing a save happening right now
+   pub fn now(hostname: Option<String>, niri_version: Option<String>) -> Self {
+      Self {
+         hostname,
+         timestamp: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .ok()
+            .map(|elapsed| elapsed.as_secs()),
+         niri_version,
+      }
+   }
+}
+
+/// Parse a session file of any known schema version, upgrading it to
+/// [`CURRENT_VERSION`].
+pub fn parse(data: &str) -> eyre::Result<SessionFile> {
+   let session = serde_json::from_str::<Value>(data).wrap_err("Session file is not valid JSON")?;
+   let session = migrate(session)?;
+   serde_json::from_value(session).wrap_err("Failed to load session data")
+}
+
+/// Schema version of a raw session file. Version 0 files are a bare array of
+/// windows without a surrounding document.
+fn version_of(session: &Value) -> eyre::Result<u32> {
+   match *session {
+      Value::Array(_) => Ok(0),
+      Value::Object(ref document) => {
+         document
+            .get("version")
+            .and_then(Value::as_u64)
+            .and_then(|version| u32::try_from(version).ok())
+            .ok_or_else(|| eyre::eyre!("Session file has no valid `version` field"))
+      },
+      _ => eyre::bail!("Session file has an unknown layout"),
+   }
+}
+
+fn migrate(mut session: Value) -> eyre::Result<Value> {
+   let version = version_of(&session)?;
+   if version > CURRENT_VERSION {
+      eyre::bail!(
+         "Session file has version {version}, but this version of nirinit only supports up to \
+          {CURRENT_VERSION}"
+      );
+   }
+
+   for (from, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+      session = migration(session)
+         .wrap_err_with(|| format!("Failed to migrate session file from version {from}"))?;
+   }
+
+   Ok(session)
+}
+
+/// Version 0 is a bare array of windows. The earliest files also lack `title`,
+/// `window_size` and `layout_position`, which were added later on.
+fn migrate_v0_to_v1(session: Value) -> eyre::Result<Value> {
+   let Value::Array(windows) = session else {
+      eyre::bail!("Expected a list of windows");
+   };
+
+   let windows = windows
+      .into_iter()
+      .map(|window| {
+         let Value::Object(mut window) = window else {
+            eyre::bail!("Expected a window object");
+         };
+         for field in ["title", "window_size", "layout_position"] {
+            window.entry(field).or_insert(Value::Null);
+         }
+         Ok(Value::Object(window))
+      })
+      .collect::<eyre::Result<Vec<_>>>()?;
+
+   let mut document = Map::new();
+   document.insert("version".to_owned(), Value::from(1));
+   document.insert(
+      "metadata".to_owned(),
+      serde_json::to_value(SessionMetadata::default())?,
+   );
+   document.insert("windows".to_owned(), Value::Array(windows));
+   Ok(Value::Object(document))
+}
+
+/// Version 2 records whether a window is floating, and where.
+fn migrate_v1_to_v2(session: Value) -> eyre::Result<Value> {
+   migrate_windows(session, 2, |window| {
+      window.entry("is_floating").or_insert(Value::Bool(false));
+      window.entry("floating_position").or_insert(Value::Null);
+   })
+}
+
+/// Version 3 records column widths relative to the output, the column display
+/// mode and full-width columns.
+fn migrate_v2_to_v3(session: Value) -> eyre::Result<Value> {
+   migrate_windows(session, 3, |window| {
+      window.entry("column_width").or_insert(Value::Null);
+      window.entry("column_display").or_insert(Value::Null);
+      window.entry("is_full_width").or_insert(Value::Bool(false));
+   })
+}
+
+/// Version 4 records the working directory and command line of the window's
+/// process.
+fn migrate_v3_to_v4(session: Value) -> eyre::Result<Value> {
+   migrate_windows(session, 4, |window| {
+      window.entry("cwd").or_insert(Value::Null);
+      window.entry("cmdline").or_insert(Value::Null);
+   })
+}
+
+/// Version 5 records the multiplexer session of terminal windows.
+fn migrate_v4_to_v5(session: Value) -> eyre::Result<Value> {
+   migrate_windows(session, 5, |window| {
+      window.entry("multiplexer").or_insert(Value::Null);
+   })
+}
+
+/// Apply `upgrade` to every window of a session document and set its version.
+fn migrate_windows(
+   session: Value,
+   version: u32,
+   upgrade: impl Fn(&mut Map<String, Value>),
+) -> eyre::Result<Value> {
+   let Value::Object(mut document) = session else {
+      eyre::bail!("Expected a session document");
+   };
+
+   let Some(&mut Value::Array(ref mut windows)) = document.get_mut("windows") else {
+      eyre::bail!("Expected a list of windows");
+   };
+   for window in windows {
+      let Value::Object(ref mut window) = *window else {
+         eyre::bail!("Expected a window object");
+      };
+      upgrade(window);
+   }
+
+   document.insert("version".to_owned(), Value::from(version));
+   Ok(Value::Object(document))
+}
+
+#[cfg(test)]
+mod tests {
+   use serde_json::json;
+
+   use super::*;
+
+   /// A window as the earliest versions wrote it
+   fn v0_window() -> Value {
+      json!({
+         "id": 1,
+         "app_id": "kitty",
+         "launch_command": "kitty",
+         "workspace_idx": 2,
+         "workspace_name": "dev",
+         "workspace_output": "DP-1",
+         "is_focused": true,
+      })
+   }
+
+   fn document(version: u32, window: &Value) -> String {
+      json!({
+         "version": version,
+         "metadata": { "hostname": "host", "timestamp": 1, "niri_version": "25.11" },
+         "windows": [window],
+      })
+      .to_string()
+   }
+
+   fn only_window(session: SessionFile) -> SessionWindow {
+      assert_eq!(session.version, CURRENT_VERSION);
+      assert_eq!(session.windows.len(), 1);
+      session.windows.into_iter().next().unwrap()
+   }
+
+   /// Fields added in versions 2 to 5 have their defaults
+   fn assert_defaults_since(window: &SessionWindow, version: u32) {
+      if version < 2 {
+         assert!(!window.is_floating);
+         assert_eq!(window.floating_position, None);
+      }
+      if version < 3 {
+         assert_eq!(window.column_width, None);
+         assert_eq!(window.column_display, None);
+         assert!(!window.is_full_width);
+      }
+      if version < 4 {
+         assert_eq!(window.cwd, None);
+         assert_eq!(window.cmdline, None);
+      }
+      if version < 5 {
+         assert!(window.multiplexer.is_none());
+      }
+   }
+
+   #[test]
+   fn parses_v0_without_optional_fields() {
+      let data = json!([v0_window()]).to_string();
+      let session = parse(&data).unwrap();
+      assert_eq!(session.metadata.hostname, None);
+
+      let window = only_window(session);
+      assert_eq!(window.app_id.as_deref(), Some("kitty"));
+      assert_eq!(window.workspace_idx, Some(2));
+      assert_eq!(window.title, None);
+      assert_eq!(window.window_size, None);
+      assert_eq!(window.layout_position, None);
+      assert_defaults_since(&window, 0);
+   }
+
+   #[test]
+   fn parses_v0_with_optional_fields() {
+      let mut window = v0_window();
+      window["title"] = json!("~/src");
+      window["window_size"] = json!([800, 600]);
+      window["layout_position"] = json!([3, 1]);
+      let data = json!([window]).to_string();
+
+      let window = only_window(parse(&data).unwrap());
+      assert_eq!(window.title.as_deref(), Some("~/src"));
+      assert_eq!(window.window_size, Some((800, 600)));
+      assert_eq!(window.layout_position, Some((3, 1)));
+      assert_defaults_since(&window, 0);
+   }
+
+   #[test]
+   fn upgrades_v1() {
+      let mut window = v0_window();
+      window["title"] = json!("zsh");
+      window["window_size"] = Value::Null;
+      window["layout_position"] = json!([1, 2]);
+
+      let session = parse(&document(1, &window)).unwrap();
+      assert_eq!(session.metadata.hostname.as_deref(), Some("host"));
+      let window = only_window(session);
+      assert_eq!(window.layout_position, Some((1, 2)));
+      assert_defaults_since(&window, 1);
+   }
+
+   #[test]
+   fn upgrades_v2() {
+      let mut window = v0_window();
+      window["title"] = json!("zsh");
+      window["window_size"] = json!([400, 300]);
+      window["layout_position"] = Value::Null;
+      window["is_floating"] = json!(true);
+      window["floating_position"] = json!([10.0, 20.0]);
+
+      let window = only_window(parse(&document(2, &window)).unwrap());
+      assert!(window.is_floating);
+      assert_eq!(window.floating_position, Some((10.0, 20.0)));
+      assert_defaults_since(&window, 2);
+   }
+
+   #[test]
+   fn upgrades_v3() {
+      let mut window = v0_window();
+      window["title"] = json!("zsh");
+      window["window_size"] = Value::Null;
+      window["layout_position"] = json!([1, 1]);
+      window["is_floating"] = json!(false);
+      window["floating_position"] = Value::Null;
+      window["column_width"] = json!(0.5);
+      window["column_display"] = json!("Tabbed");
+      window["is_full_width"] = json!(false);
+
+      let window = only_window(parse(&document(3, &window)).unwrap());
+      assert_eq!(window.column_width, Some(0.5));
+      assert_eq!(window.column_display, Some(ColumnDisplay::Tabbed));
+      assert_defaults_since(&window, 3);
+   }
+
+   #[test]
+   fn upgrades_v4() {
+      let mut window = v0_window();
+      window["title"] = json!("zsh");
+      window["window_size"] = Value::Null;
+      window["layout_position"] = json!([1, 1]);
+      window["is_floating"] = json!(false);
+      window["floating_position"] = Value::Null;
+      window["column_width"] = Value::Null;
+      window["column_display"] = Value::Null;
+      window["is_full_width"] = json!(true);
+      window["cwd"] = json!("/tmp");
+      window["cmdline"] = json!(["kitty", "--single-instance"]);
+
+      let window = only_window(parse(&document(4, &window)).unwrap());
+      assert!(window.is_full_width);
+      assert_eq!(window.cwd.as_deref(), Some("/tmp"));
+      assert_eq!(
+         window.cmdline,
+         Some(vec!["kitty".to_owned(), "--single-instance".to_owned()])
+      );
+      assert_defaults_since(&window, 4);
+   }
+
+   #[test]
+   fn rejects_newer_versions() {
+      let data = document(CURRENT_VERSION + 1, &v0_window());
+      let err = parse(&data).err().unwrap();
+      assert!(err.to_string().contains("only supports up to"));
+   }
+}
@@ -0,0 +1,72 @@
+/// Longest host name allowed by DNS
+const MAX_HOSTNAME_LEN: usize = 253;
+
+/// Whether `hostname` looks like a host name or SSH alias that is safe to pass
+/// to `ssh`. Names starting with `-` would be taken for options.
+pub fn is_valid_hostname(hostname: &str) -> bool {
+   hostname.len() <= MAX_HOSTNAME_LEN
+      && hostname
+         .chars()
+         .next()
+         .is_some_and(|first| first.is_ascii_alphanumeric())
+      && hostname
+         .chars()
+         .all(|ch| ch.is_ascii_alphanumeric() || matches!(ch, '.' | '-' | '_'))
+}
+
+/// Whether `name` looks like a terminal multiplexer session name. Names are
+/// always quoted in generated scripts, this only keeps garbage parsed out of a
+/// window title from being passed on.
+pub fn is_valid_session_name(name: &str) -> bool {
+   !name.is_empty() && !name.starts_with('-') && !name.chars().any(char::is_control)
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   #[test]
+   fn accepts_host_names_and_aliases() {
+      for hostname in [
+         "surfi1",
+         "build.example.com",
+         "my-host",
+         "dev_box",
+         "10.0.0.1",
+      ] {
+         assert!(is_valid_hostname(hostname), "{hostname}");
+      }
+   }
+
+   #[test]
+   fn rejects_unsafe_host_names() {
+      let too_long = "a".repeat(MAX_HOSTNAME_LEN + 1);
+      for hostname in [
+         "",
+         "-oProxyCommand=reboot",
+         ".host",
+         "host;reboot",
+         "host reboot",
+         "$(reboot)",
+         "`reboot`",
+         "ho\x1bst",
+         &too_long,
+      ] {
+         assert!(!is_valid_hostname(hostname), "{hostname:?}");
+      }
+   }
+
+   #[test]
+   fn accepts_session_names_with_shell_syntax() {
+      for name in ["main", "x; rm -rf ~", "$(reboot)", "`reboot`", "it's"] {
+         assert!(is_valid_session_name(name), "{name}");
+      }
+   }
+
+   #[test]
+   fn rejects_garbage_session_names() {
+      for name in ["", "-t", "ma\nin", "ma\x1b]0;in", "\x07"] {
+         assert!(!is_valid_session_name(name), "{name:?}");
+      }
+   }
+}
@@ -0,0 +1,182 @@
+use std::{
+   collections::HashMap,
+   fs,
+   mem,
+};
+
+use serde::Deserialize;
+
+/// The `[ssh]` section of the config
+#[derive(Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SshConfig {
+   /// Map host names, as shown in window titles, to the SSH alias to connect
+   /// to them with
+   #[serde(default)]
+   aliases: HashMap<String, String>,
+}
+
+impl SshConfig {
+   /// The host to pass to `ssh` to reach `hostname`: an alias from the config,
+   /// or else a `Host` from `~/.ssh/config` whose `HostName` is `hostname`,
+   /// or else `hostname` itself.
+   pub fn resolve(&self, hostname: &str) -> String {
+      if let Some(alias) = self
+         .aliases
+         .iter()
+         .find(|&(name, _)| hostnames_match(name, hostname))
+         .map(|(_, alias)| alias)
+      {
+         return alias.clone();
+      }
+
+      ssh_config_hosts()
+         .into_iter()
+         .find(|&(ref alias, ref host)| {
+            hostnames_match(host, hostname) && !hostnames_match(alias, hostname)
+         })
+         .map_or_else(|| hostname.to_owned(), |(alias, _)| alias)
+   }
+}
+
+/// Whether two host names refer to the same machine, ignoring case and
+/// treating a short name like `box` as equal to a qualified one like
+/// `box.example.com`.
+pub fn hostnames_match(left: &str, right: &str) -> bool {
+   if left.eq_ignore_ascii_case(right) {
+      return true;
+   }
+   if left.contains('.') && right.contains('.') {
+      return false;
+   }
+
+   let short = |name: &str| name.split('.').next().unwrap_or(name).to_ascii_lowercase();
+   short(left) == short(right)
+}
+
+/// The `Host` aliases of `~/.ssh/config` along with their `HostName`
+fn ssh_config_hosts() -> Vec<(String, String)> {
+   dirs::home_dir()
+      .and_then(|home| fs::read_to_string(home.join(".ssh/config")).ok())
+      .map_or_else(Vec::new, |config| parse_hosts(&config))
+}
+
+/// The `Host` aliases of an SSH config along with their `HostName`. Aliases
+/// containing wildcards are left out, as they can't be connected to.
+fn parse_hosts(config: &str) -> Vec<(String, String)> {
+   let mut hosts = Vec::new();
+   let mut aliases = Vec::new();
+   for line in config.lines() {
+      let line = line.trim();
+      if line.is_empty() || line.starts_with('#') {
+         continue;
+      }
+
+      // Keywords are case-insensitive and separated from their arguments by
+      // whitespace or `=`
+      let Some((keyword, args)) = line.split_once(|ch: char| ch.is_whitespace() || ch == '=')
+      else {
+         continue;
+      };
+      let args = args.trim_start_matches(|ch: char| ch.is_whitespace() || ch == '=');
+
+      if keyword.eq_ignore_ascii_case("Host") {
+         aliases = args
+            .split_whitespace()
+            .filter(|alias| !alias.contains(['*', '?', '!']))
+            .map(str::to_owned)
+            .collect();
+      } else if keyword.eq_ignore_ascii_case("Match") {
+         aliases.clear();
+      } else if keyword.eq_ignore_ascii_case("HostName") {
+         let hostname = args.trim().trim_matches('"');
+         hosts.extend(
+            mem::take(&mut aliases)
+               .into_iter()
+               .map(|alias| (alias, hostname.to_owned())),
+         );
+      }
+   }
+
+   hosts
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   fn hosts(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+      pairs
+         .iter()
+         .map(|&(alias, host)| (alias.to_owned(), host.to_owned()))
+         .collect()
+   }
+
+   #[test]
+   fn parses_every_alias_of_a_host() {
+      let config = "\
+# Work machines
+Host box box-work
+   HostName box.example.com
+   User me
+
+Host db
+   HostName db.example.com
+";
+      assert_eq!(
+         parse_hosts(config),
+         hosts(&[
+            ("box", "box.example.com"),
+            ("box-work", "box.example.com"),
+            ("db", "db.example.com"),
+         ])
+      );
+   }
+
+   #[test]
+   fn leaves_out_wildcard_aliases() {
+      let config = "\
+Host *.example.com box? !db web
+   HostName web.example.com
+Host *
+   HostName fallback.example.com
+";
+      assert_eq!(parse_hosts(config), hosts(&[("web", "web.example.com")]));
+   }
+
+   #[test]
+   fn forgets_aliases_at_match() {
+      let config = "\
+Host box
+Match user me
+   HostName other.example.com
+Host db
+   HostName db.example.com
+";
+      assert_eq!(parse_hosts(config), hosts(&[("db", "db.example.com")]));
+   }
+
+   #[test]
+   fn accepts_equals_signs_and_any_case() {
+      let config = "\
+Host=box
+   hostname = \"box.example.com\"
+HOST db
+   HostName=db.example.com
+";
+      assert_eq!(
+         parse_hosts(config),
+         hosts(&[("box", "box.example.com"), ("db", "db.example.com")])
+      );
+   }
+
+   #[test]
+   fn matches_short_and_qualified_hostnames() {
+      assert!(hostnames_match("box", "box"));
+      assert!(hostnames_match("Box.Example.com", "box.example.com"));
+      assert!(hostnames_match("box", "box.example.com"));
+      assert!(hostnames_match("box.example.com", "BOX"));
+      assert!(!hostnames_match("box.example.com", "box.example.org"));
+      assert!(!hostnames_match("box", "db.example.com"));
+   }
+}
@@ -0,0 +1,166 @@
+use std::{
+   ffi::OsString,
+   fs::{
+      self,
+      File,
+   },
+   io::{
+      self,
+      Write as _,
+   },
+   path::{
+      Path,
+      PathBuf,
+   },
+   time::Duration,
+};
+
+/// Shortest time between two backups. Saves in between only replace the
+/// current file, so a burst of saves doesn't push older layouts out.
+const BACKUP_INTERVAL: Duration = Duration::from_mins(15);
+
+/// Path of the `generation`-th backup of `path`, e.g. `session.json.2`.
+pub fn backup_path(path: &Path, generation: usize) -> PathBuf {
+   let mut file_name = path.file_name().map(OsString::from).unwrap_or_default();
+   file_name.push(format!(".{generation}"));
+   path.with_file_name(file_name)
+}
+
+/// Path of the temporary file `path` is written to before replacing it.
+fn tmp_path(path: &Path) -> PathBuf {
+   let mut tmp_name = path.file_name().map(OsString::from).unwrap_or_default();
+   tmp_name.push(".tmp");
+   path.with_file_name(tmp_name)
+}
+
+/// Atomically replace the contents of `path`, keeping up to `backups` previous
+/// versions as `path.1` (newest) to `path.N` (oldest), at most one every
+/// `BACKUP_INTERVAL`.
+///
+/// The data is written to a temporary file, flushed to disk and renamed over
+/// the original, so a crash mid-write never leaves a truncated file behind.
+pub fn write_atomic(path: &Path, contents: &[u8], backups: usize) -> io::Result<()> {
+   let tmp_path = tmp_path(path);
+
+   let mut tmp = File::create(&tmp_path)?;
+   tmp.write_all(contents)?;
+   tmp.sync_all()?;
+   drop(tmp);
+
+   if backups > 0 && path.exists() && backup_due(path) {
+      rotate_backups(path, backups)?;
+   }
+
+   fs::rename(&tmp_path, path)?;
+
+   // Make sure the rename itself is persisted
+   if let Some(dir) = path.parent() {
+      File::open(dir)?.sync_all()?;
+   }
+
+   Ok(())
+}
+
+/// Remove `path` along with its backups and any leftover temporary file.
+pub fn remove(path: &Path) -> io::Result<()> {
+   fs::remove_file(path)?;
+   for generation in 1.. {
+      match fs::remove_file(backup_path(path, generation)) {
+         Ok(()) => {},
+         Err(err) if err.kind() == io::ErrorKind::NotFound => break,
+         Err(err) => return Err(err),
+      }
+   }
+   match fs::remove_file(tmp_path(path)) {
+      Ok(()) => Ok(()),
+      Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+      Err(err) => Err(err),
+   }
+}
+
+/// Whether the newest backup of `path` is missing or older than
+/// `BACKUP_INTERVAL`.
+fn backup_due(path: &Path) -> bool {
+   fs::metadata(backup_path(path, 1))
+      .and_then(|metadata| metadata.modified())
+      .ok()
+      .and_then(|modified| modified.elapsed().ok())
+      .is_none_or(|age| age >= BACKUP_INTERVAL)
+}
+
+/// Shift `path.1..N-1` to `path.2..N` and make the current file `path.1`.
+///
+/// The current file is hard linked rather than moved, so `path` keeps existing
+/// until it is atomically replaced.
+fn rotate_backups(path: &Path, backups: usize) -> io::Result<()> {
+   for generation in (1..backups).rev() {
+      let from = backup_path(path, generation);
+      if from.exists() {
+         fs::rename(&from, backup_path(path, generation + 1))?;
+      }
+   }
+
+   let newest = backup_path(path, 1);
+   match fs::remove_file(&newest) {
+      Ok(()) => {},
+      Err(err) if err.kind() == io::ErrorKind::NotFound => {},
+      Err(err) => return Err(err),
+   }
+   if fs::hard_link(path, &newest).is_err() {
+      fs::copy(path, &newest)?;
+   }
+
+   Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+   use std::{
+      env,
+      process,
+   };
+
+   use super::*;
+
+   /// A fresh directory for a test to write files in
+   fn test_dir(name: &str) -> PathBuf {
+      let dir = env::temp_dir().join(format!("nirinit-{name}-{}", process::id()));
+      let _ = fs::remove_dir_all(&dir);
+      fs::create_dir_all(&dir).unwrap();
+      dir
+   }
+
+   #[test]
+   fn backs_up_at_most_once_per_interval() {
+      let dir = test_dir("backups");
+      let path = dir.join("session.json");
+      write_atomic(&path, b"first", 3).unwrap();
+      write_atomic(&path, b"second", 3).unwrap();
+      write_atomic(&path, b"third", 3).unwrap();
+
+      assert_eq!(fs::read(&path).unwrap(), b"third");
+      assert_eq!(fs::read(backup_path(&path, 1)).unwrap(), b"first");
+      assert!(!backup_path(&path, 2).exists());
+      fs::remove_dir_all(dir).unwrap();
+   }
+
+   #[test]
+   fn removes_backups_and_temporary_file() {
+      let dir = test_dir("remove");
+      let path = dir.join("work.json");
+      for generation in 1..=3 {
+         fs::write(backup_path(&path, generation), b"old").unwrap();
+      }
+      fs::write(&path, b"current").unwrap();
+      fs::write(tmp_path(&path), b"partial").unwrap();
+      fs::write(dir.join("other.json"), b"other").unwrap();
+
+      remove(&path).unwrap();
+      let left = fs::read_dir(&dir)
+         .unwrap()
+         .map(|entry| entry.unwrap().file_name())
+         .collect::<Vec<_>>();
+      assert_eq!(left, ["other.json"]);
+      fs::remove_dir_all(dir).unwrap();
+   }
+}